@@ -1,34 +1,113 @@
 use influent::create_client;
 
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 use std::sync::mpsc::{channel, Sender};
-use influent::client::{Client, Credentials};
+use influent::client::{Client, Credentials, Precision};
 use influent::measurement::{Measurement, Value};
+use influent::serializer::Serializer;
+use influent::serializer::line::LineSerializer;
 use chrono::{DateTime, Utc};
+use serde_json;
+use config::Connection;
 use error::{ConvertError, ConvertResult};
+use mapper::Sink;
+
+/// A typed influxdb field value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FieldValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl FieldValue {
+    fn as_value(&self) -> Value<'_> {
+        match *self {
+            FieldValue::String(ref s) => Value::String(s),
+            FieldValue::Integer(i) => Value::Integer(i),
+            FieldValue::Float(f) => Value::Float(f),
+            FieldValue::Boolean(b) => Value::Boolean(b),
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FieldValue::String(ref s) => write!(f, "{}", s),
+            FieldValue::Integer(i) => write!(f, "{}", i),
+            FieldValue::Float(v) => write!(f, "{}", v),
+            FieldValue::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
 
 /// Basic format for passing messages to
 /// the influxdb client.
 /// The value has as first field the name and then the value.
 /// Tags follow the same combination.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
-    time: i64, // unix timestamp in seconds
-    value: (String, String),
+    series: String,
+    time: i64, // unix timestamp in nanoseconds
+    value: (String, FieldValue),
     tags: Vec<(String, String)>,
 }
 
 impl Message {
     pub fn new(
+        series: String,
         time: DateTime<Utc>,
-        value: (String, String),
+        value: (String, FieldValue),
         tags: Vec<(String, String)>,
     ) -> Message {
         Message {
-            time: time.timestamp(),
-            value: value,
-            tags: tags,
+            series,
+            time: time
+                .timestamp_nanos_opt()
+                .unwrap_or_else(|| time.timestamp().saturating_mul(1_000_000_000)),
+            value,
+            tags,
+        }
+    }
+
+    pub fn series(&self) -> &str {
+        &self.series
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_nanos(self.time)
+    }
+
+    pub fn value(&self) -> &(String, FieldValue) {
+        &self.value
+    }
+
+    pub fn tags(&self) -> &[(String, String)] {
+        &self.tags
+    }
+
+    /// Convert the message into an influxdb measurement.
+    fn measurement(&self) -> Measurement<'_> {
+        let mut measure = Measurement::new(&self.series);
+        measure.add_field(self.value.0.as_str(), self.value.1.as_value());
+        measure.set_timestamp(self.time);
+        for tag in &self.tags {
+            measure.add_tag(tag.0.as_str(), tag.1.as_str());
         }
+        measure
+    }
+
+    /// Return the message in the influxdb line protocol.
+    pub fn line(&self) -> String {
+        LineSerializer::new().serialize(&self.measurement())
     }
 }
 
@@ -38,6 +117,10 @@ impl Message {
 pub struct InfluxClient {
     tx: Sender<Option<Message>>,
     thread_handle: thread::JoinHandle<()>,
+    confirmed: Arc<AtomicUsize>,
+    sent: AtomicUsize,
+    handled: Arc<AtomicUsize>,
+    failures: Arc<Mutex<Vec<String>>>,
 }
 
 impl InfluxClient {
@@ -50,23 +133,52 @@ impl InfluxClient {
             .and_then(|()| self.thread_handle.join().map_err(ConvertError::Join))
     }
 
+    /// The number of sent messages which were written to influxdb.
+    /// The count stops at the first message which failed to write,
+    /// so all messages before the count are known to be stored.
+    pub fn confirmed(&self) -> Arc<AtomicUsize> {
+        self.confirmed.clone()
+    }
+
+    /// Wait until the client handled all messages sent so far and
+    /// return the errors of the writes which failed since the last flush.
+    pub fn flush(&self) -> Vec<String> {
+        while self.handled.load(Ordering::SeqCst) < self.sent.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.failures
+            .lock()
+            .map(|mut f| f.drain(..).collect())
+            .unwrap_or_default()
+    }
+
     /// Convenient method for sending data to running background influx client.
     pub fn send(&self, msg: Message) -> ConvertResult<()> {
-        self.tx.send(Some(msg)).map_err(ConvertError::Send)
+        self.tx.send(Some(msg)).map_err(ConvertError::Send)?;
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        Ok(())
     }
 
     /// Construct a new influx db background client
     /// which accepts messages and stores them.
     /// It stops if it receives a None message.
+    /// Messages which could not be written are appended
+    /// to the journal file if some is given.
     pub fn new(
         hosts: String,
         user: String,
         pass: String,
         db: String,
-        series: String,
+        journal: Option<String>,
     ) -> ConvertResult<InfluxClient> {
         let (tx, rx) = channel();
         let thread = thread::Builder::new();
+        let confirmed = Arc::new(AtomicUsize::new(0));
+        let count = confirmed.clone();
+        let handled = Arc::new(AtomicUsize::new(0));
+        let done = handled.clone();
+        let failures = Arc::new(Mutex::new(vec![]));
+        let failed_writes = failures.clone();
 
         // create a client
         let handle = thread.spawn(move || {
@@ -79,6 +191,7 @@ impl InfluxClient {
                 vec![&hosts],
             );
 
+            let mut failed = false;
             loop {
                 let msg: Option<Message> = match rx.recv() {
                     Ok(m) => m,
@@ -95,33 +208,74 @@ impl InfluxClient {
                 };
 
                 debug!(format!("Incoming: {:?}", m));
-                let mut measure = Measurement::new(&series);
-                measure.add_field(m.value.0.as_str(), Value::String(&m.value.1));
-                measure.set_timestamp(m.time * 1000000000); // convert to nanoseconds
-                for ref tag in &m.tags {
-                    measure.add_tag(tag.0.as_str(), tag.1.as_str());
-                }
-
-                if let Err(e) = client.write_one(measure, None) {
+                if let Err(e) = client.write_one(m.measurement(), None) {
+                    failed = true;
                     error!(format!("Failed to write to influxdb. {:?}", e));
+                    if let Ok(mut f) = failed_writes.lock() {
+                        f.push(format!("Failed to write to influxdb. {:?}", e));
+                    }
+                    if let Some(ref j) = journal {
+                        if let Err(e) = append_journal(j, &m) {
+                            error!(format!("Failed to write journal {}. {}", j, e));
+                        }
+                    }
+                } else if !failed {
+                    count.fetch_add(1, Ordering::SeqCst);
                 }
+                done.fetch_add(1, Ordering::SeqCst);
             }
         });
 
         handle.map_err(ConvertError::Influx).and_then(|handle| {
             Ok(InfluxClient {
                 thread_handle: handle,
-                tx: tx,
+                tx,
+                confirmed,
+                sent: AtomicUsize::new(0),
+                handled,
+                failures,
             })
         })
     }
 }
 
+/// Append a message as json line to the journal file.
+fn append_journal(journal: &str, msg: &Message) -> ConvertResult<()> {
+    let line = serde_json::to_string(msg).map_err(|e| ConvertError::Import(format!("{}", e)))?;
+    let mut file = OpenOptions::new().create(true).append(true).open(journal)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Run a single query against influxdb and return the parsed
+/// json response. Timestamps are returned as unix seconds.
+pub fn query(con: &Connection, q: &str) -> ConvertResult<serde_json::Value> {
+    let client = create_client(
+        Credentials {
+            username: &con.user,
+            password: &con.password,
+            database: &con.database,
+        },
+        vec![&con.server],
+    );
+
+    debug!(format!("Query: {}", q));
+    let res = client
+        .query(q.into(), Some(Precision::Seconds))
+        .map_err(|e| ConvertError::Client(format!("{:?}", e)))?;
+    serde_json::from_str(&res).map_err(|e| ConvertError::Client(format!("{}", e)))
+}
+
+impl Sink for InfluxClient {
+    fn send(&self, msg: Message) -> ConvertResult<()> {
+        InfluxClient::send(self, msg)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
     use std::time::Duration;
-    use chrono::NaiveDateTime;
 
     // clear and create the test db instance
     fn clean_db() {
@@ -145,7 +299,7 @@ pub mod test {
             "testuser".into(),
             "testpass".into(),
             "test".into(),
-            "try".into(),
+            None,
         ).unwrap()
     }
 
@@ -163,20 +317,46 @@ pub mod test {
         let res = client
             .query("select last(*) from try".into(), None)
             .unwrap();
-        let time = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(msg.time, 0), Utc);
+        let time = msg.time();
         let json_msg = format!("{{\"results\":[{{\"statement_id\":0,\"series\":[{{\"name\":\"try\",\"columns\":[\"time\",\"last_{}\"],\"values\":[[\"{:?}\",\"{}\"]]}}]}}]}}\n", msg.value.0, time, msg.value.1);
         assert_eq!(res, json_msg);
     }
 
+    #[test]
+    fn test_line_protocol() {
+        let time = DateTime::from_timestamp(1451603700, 0).unwrap();
+        let msg = Message::new(
+            "meter".into(),
+            time,
+            ("Profilwert kWh".into(), FieldValue::Float(108.0)),
+            vec![("Status".into(), "220".into())],
+        );
+        assert_eq!(msg.time(), time);
+        assert_eq!(
+            msg.line(),
+            "meter,Status=220 Profilwert\\ kWh=108 1451603700000000000"
+        );
+    }
+
     #[test]
     fn test_simple_import() {
         // try insert into test influxdb and query result
         let client = start_client();
-        let msg = Message::new(Utc::now(), ("power".into(), "1".into()), vec![]);
+        let msg = Message::new(
+            "try".into(),
+            Utc::now(),
+            ("power".into(), FieldValue::String("1".into())),
+            vec![],
+        );
         assert!(client.send(msg.clone()).is_ok());
         validate(&msg);
 
-        let msg = Message::new(Utc::now(), ("power".into(), "2".into()), vec![]);
+        let msg = Message::new(
+            "try".into(),
+            Utc::now(),
+            ("power".into(), FieldValue::String("2".into())),
+            vec![],
+        );
         assert!(client.send(msg.clone()).is_ok());
         validate(&msg);
 
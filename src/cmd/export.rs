@@ -0,0 +1,146 @@
+//! Write the points of a series as csv.
+use std::fs::File;
+use std::io::{self, Write};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use super::*;
+use client;
+
+pub const USAGE: &str = concat!(
+    "
+Write all points of a series as csv which can be read by `import`
+with the same options.
+
+Usage:
+  x-influx export [options] <series>
+  x-influx export (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+  -o, --output FILE    Write to FILE instead of stdout.
+  --from TIME          Only export points at or after TIME (RFC 3339).
+  --to TIME            Only export points before TIME (RFC 3339).
+  -T, --time VAL       Name of the timestamp column (default: timestamp).
+  -f, --format FMT     The timestamp format (default: %F %H:%M:%S).
+  --timezone TZ        Timezone of the timestamps (default: UTC).
+  -D, --delimiter DEL  Use another csv delimiter (default: ,).
+",
+    connection_options!()
+);
+
+/// Build the query with the optional time range.
+fn select(series: &str, from: Option<&str>, to: Option<&str>) -> ConvertResult<String> {
+    let time = |t: &str| {
+        DateTime::parse_from_rfc3339(t)
+            .map(|t| t.with_timezone(&Utc).to_rfc3339())
+            .map_err(|e| ConvertError::Config(format!("Invalid time {}: {}", t, e)))
+    };
+
+    let mut conditions = vec![];
+    if let Some(f) = from {
+        conditions.push(format!("time >= '{}'", time(f)?));
+    }
+    if let Some(t) = to {
+        conditions.push(format!("time < '{}'", time(t)?));
+    }
+
+    let mut q = format!("SELECT * FROM \"{}\"", series.replace('"', "\\\""));
+    if !conditions.is_empty() {
+        q = format!("{} WHERE {}", q, conditions.join(" AND "));
+    }
+    Ok(q)
+}
+
+/// Format a single cell of the query result.
+fn cell(layout: &Layout, column: &str, value: &Value) -> ConvertResult<String> {
+    match *value {
+        Value::Number(ref n) if column == "time" => {
+            let time = n.as_i64()
+                .and_then(|t| DateTime::<Utc>::from_timestamp(t, 0))
+                .ok_or_else(|| ConvertError::Import(format!("Invalid time {}", n)))?;
+            Ok(match layout.timezone()? {
+                Some(tz) => time.with_timezone(&tz).format(&layout.tformat).to_string(),
+                None => time.format(&layout.tformat).to_string(),
+            })
+        }
+        Value::Null => Ok(String::new()),
+        Value::String(ref s) => Ok(s.clone()),
+        ref v => Ok(v.to_string()),
+    }
+}
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let layout = layout(&args)?;
+    let series = args.arg_series.clone().unwrap_or_default();
+    let q = select(&series, args.flag_from.as_deref(), args.flag_to.as_deref())?;
+    let con = connection(&args, &config(&args)?)?;
+    let res = client::query(&con, &q)?;
+
+    if let Some(e) = res.pointer("/results/0/error") {
+        return Err(ConvertError::Client(e.to_string()));
+    }
+    let result = res.pointer("/results/0/series/0")
+        .ok_or_else(|| ConvertError::NotFound(format!("Series {}", series)))?;
+    let columns: Vec<&str> = result["columns"]
+        .as_array()
+        .map(|c| c.iter().filter_map(|c| c.as_str()).collect())
+        .unwrap_or_default();
+
+    let mut out: Box<dyn Write> = match args.flag_output {
+        Some(ref f) => Box::new(File::create(f)?),
+        None => Box::new(io::stdout()),
+    };
+
+    let delimiter = layout.delimiter.to_string();
+    let header: Vec<&str> = columns
+        .iter()
+        .map(|&c| if c == "time" { layout.time.as_str() } else { c })
+        .collect();
+    writeln!(out, "{}", header.join(&delimiter))?;
+
+    let mut rows = 0;
+    for row in result["values"].as_array().into_iter().flatten() {
+        let cells = columns
+            .iter()
+            .zip(row.as_array().into_iter().flatten())
+            .map(|(c, v)| cell(&layout, c, v))
+            .collect::<ConvertResult<Vec<String>>>()?;
+        writeln!(out, "{}", cells.join(&delimiter))?;
+        rows += 1;
+    }
+    info!(format!("Exported {} points of {}", rows, series));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        assert_eq!(select("meter", None, None).unwrap(), "SELECT * FROM \"meter\"");
+        assert_eq!(
+            select("a\"b", Some("2016-01-01T00:00:00+01:00"), Some("2017-01-01T00:00:00Z"))
+                .unwrap(),
+            "SELECT * FROM \"a\\\"b\" WHERE time >= '2015-12-31T23:00:00+00:00' \
+             AND time < '2017-01-01T00:00:00+00:00'"
+        );
+        assert!(select("meter", Some("yesterday"), None).is_err());
+    }
+
+    #[test]
+    fn test_cell() {
+        let layout = Layout {
+            tformat: "%d.%m.%Y %H:%M".into(),
+            timezone: Some("CET".into()),
+            ..Layout::default()
+        };
+        let time = Value::from(1451603700);
+        assert_eq!(cell(&layout, "time", &time).unwrap(), "01.01.2016 00:15");
+        assert_eq!(cell(&layout, "data", &Value::from(103.5)).unwrap(), "103.5");
+        assert_eq!(cell(&layout, "Status", &Value::from("220")).unwrap(), "220");
+        assert_eq!(cell(&layout, "Status", &Value::Null).unwrap(), "");
+    }
+}
@@ -0,0 +1,130 @@
+//! Import csv files into influxdb.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ctrlc;
+use super::*;
+use mapper::{hash, Checkpoints, Mapper, State};
+
+/// Hash the files tracked in the state file. With `resume` the
+/// completed files are removed and the rows to resume from returned.
+fn checkpoints(
+    files: &mut Vec<String>,
+    state: &State,
+    resume: bool,
+) -> ConvertResult<(BTreeMap<String, String>, HashMap<String, usize>)> {
+    let mut hashes = BTreeMap::new();
+    let mut rows = HashMap::new();
+    let mut kept = vec![];
+    for file in files.drain(..) {
+        // stdin has no checkpoint
+        if file == "-" {
+            kept.push(file);
+            continue;
+        }
+        let hash = hash(&file)?;
+        match state.resume(&file, &hash) {
+            None if resume => {
+                info!(format!("{} is unchanged, skipped", file));
+                continue;
+            }
+            Some(row) if resume && row > 0 => {
+                info!(format!("{} resumes after row {}", file, row));
+                rows.insert(file.clone(), row);
+            }
+            _ => (),
+        }
+        hashes.insert(file.clone(), hash);
+        kept.push(file);
+    }
+    *files = kept;
+    Ok((hashes, rows))
+}
+
+pub const USAGE: &str = concat!(
+    "
+Import csv files into influxdb.
+
+Usage:
+  x-influx import [options] <file>...
+  x-influx import (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+  -j, --journal FILE   Append messages which failed to write to FILE.
+                       See `x-influx replay`.
+  -F, --follow         Keep importing new rows of the files like
+                       tail -F until interrupted. Rotated and
+                       truncated files are read again.
+  --state FILE         Keep a checkpoint of each file in FILE which
+                       advances once influxdb confirmed the rows.
+                       The last time of sqlite queries is kept too.
+  --resume             Continue the files of --state where the last
+                       run stopped, completed files which didn't
+                       change are skipped.
+",
+    connection_options!(),
+    layout_options!(),
+    format_options!(),
+    csv_options!(),
+    file_options!()
+);
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let layout = layout(&args)?;
+    let config = config(&args)?;
+    let mut files = inputs(&args)?;
+    let resume = args.flag_resume.unwrap_or(false);
+    let state = match args.flag_state {
+        Some(ref path) => Some((path.as_str(), State::load(path)?)),
+        None if resume => return Err(ConvertError::Config("--resume needs --state".into())),
+        None => None,
+    };
+    let tracked = match state {
+        Some((path, ref s)) => Some((path, checkpoints(&mut files, s, resume)?)),
+        None => None,
+    };
+
+    let mut importer = importer(&args, &config, files)?;
+    if let Some((_, (_, ref rows))) = tracked {
+        importer = importer.with_resume(rows.clone());
+    }
+    if let Some((_, ref s)) = state {
+        importer = importer.with_last(s.last.clone());
+    }
+    if args.flag_follow.unwrap_or(false) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))
+            .map_err(|e| ConvertError::Config(format!("Failed to handle signals: {}", e)))?;
+        importer = importer.with_follow(stop);
+    }
+
+    let client = client(&args, &config)?;
+    let checkpoints = match (tracked, state) {
+        (Some((path, (hashes, _))), Some((_, s))) => {
+            Some(Checkpoints::new(client.confirmed(), path, s, hashes))
+        }
+        _ => None,
+    };
+    let result = match checkpoints {
+        Some(ref c) => importer.import(&layout, &c.track(&client)),
+        None => importer.import(&layout, &client),
+    };
+    if let Err(e) = result {
+        error!(format!("Import failed {}", e));
+    }
+
+    shutdown(client)?;
+    if let Some(c) = checkpoints {
+        let mut state = c.save()?;
+        // queries read again from the former time if some write failed
+        if c.confirmed_all() && state.last != importer.last() {
+            state.last = importer.last();
+            state.save(args.flag_state.unwrap_or_default())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Propose a layout for some sample file.
+use std::path::Path;
+
+use super::*;
+use mapper::{infer, sample};
+
+pub const USAGE: &str = "
+Guess the delimiter, header row, timestamp column and format
+and the types of all columns from the first lines of a file.
+Numeric columns are proposed as measure, the others as tags.
+The layout is printed or saved as file for --layout.
+
+Usage:
+  x-influx infer [options] <file>
+  x-influx infer (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+  -n, --rows NUM       Number of lines to sample (default: 100).
+  -S, --series VAL     Name of the measurement series
+                       (default: the file name).
+  -o, --output FILE    Save the layout to FILE.
+  --encoding ENC       Encoding of the file, e.g. utf-8 or windows-1252
+                       (default: auto).
+";
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let file = files(&args).pop().unwrap_or_default();
+    let rows = args.flag_rows.unwrap_or(100);
+    let lines = sample(&file, rows, args.flag_encoding.as_deref())?;
+    let mut inference = infer(&lines)?;
+    inference.layout.encoding = args.flag_encoding.clone();
+
+    inference.layout.series = match args.flag_series {
+        Some(ref s) => s.clone(),
+        None => Path::new(&file)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or(inference.layout.series),
+    };
+
+    println!("Columns:");
+    for (name, class) in &inference.columns {
+        println!("  {:<24} {}", name, class);
+    }
+
+    match args.flag_output {
+        Some(ref out) => {
+            inference.layout.save(out)?;
+            info!(format!("Layout written to {}", out));
+        }
+        None => {
+            let toml = ::toml::to_string(&inference.layout)
+                .map_err(|e| ConvertError::Config(format!("{}", e)))?;
+            println!("\nLayout:\n{}", toml);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,209 @@
+//! Show how a file is mapped without writing.
+use std::cell::RefCell;
+use std::io::BufRead;
+
+use super::*;
+use client::Message;
+use mapper::{Csv, Json, Legends, Sink};
+
+pub const USAGE: &str = concat!(
+    "
+Show the header row of csv files, how its columns are mapped
+by the layout and the first points as table and line protocol.
+Rows which can't be converted are listed with their error.
+Nothing is written to influxdb.
+
+Usage:
+  x-influx inspect [options] <file>...
+  x-influx inspect (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+  -n, --rows NUM       Number of data rows to read (default: 10).
+  -c, --config FILE    Read layout rules from FILE instead of
+                       ~/.config/x-influx/config.toml.
+",
+    layout_options!(),
+    format_options!(),
+    csv_options!(),
+    file_options!()
+);
+
+/// Collect the points and errors of a single file.
+#[derive(Debug, Default)]
+struct Inspection {
+    points: RefCell<Vec<Message>>,
+    errors: RefCell<Vec<String>>,
+}
+
+impl Sink for Inspection {
+    fn send(&self, msg: Message) -> ConvertResult<()> {
+        self.points.borrow_mut().push(msg);
+        Ok(())
+    }
+
+    fn reject(&self, source: &str, row: usize, err: ConvertError) {
+        self.errors
+            .borrow_mut()
+            .push(format!("{}:{}: {}", source, row, err));
+    }
+}
+
+/// Describe how some column is used by the layout.
+fn role(layout: &Layout, column: &str) -> String {
+    if column == layout.time {
+        format!("time ({})", layout.tformat)
+    } else if column == layout.measure {
+        format!("measure ({})", layout.field_type(column).name())
+    } else if layout.tags.iter().any(|t| t == column) {
+        "tag".into()
+    } else {
+        "-".into()
+    }
+}
+
+/// Print rows with aligned columns.
+fn table(rows: &[Vec<String>]) {
+    let mut widths = vec![];
+    for row in rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let cells: Vec<String> = row.iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{:<w$}", c, w = w))
+            .collect();
+        println!("    {}", cells.join("  ").trim_end());
+    }
+}
+
+/// The points as table rows with a header row.
+fn points(layout: &Layout, points: &[Message]) -> Vec<Vec<String>> {
+    let mut header = vec!["time".to_string(), layout.measure.clone()];
+    header.extend(layout.tags.iter().cloned());
+
+    let mut rows = vec![header];
+    for p in points {
+        let mut row = vec![p.time().to_rfc3339(), p.value().1.to_string()];
+        for tag in &layout.tags {
+            let value = p.tags().iter().find(|t| &t.0 == tag).map(|t| t.1.clone());
+            row.push(value.unwrap_or_default());
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+/// Read the preamble, header and the first rows of some file once,
+/// so that stdin can be inspected as well.
+fn head(csv: &Csv, file: &str, layout: &Layout, rows: usize) -> ConvertResult<String> {
+    let mut text = String::new();
+    for line in csv.open(file, layout)?.lines().take(layout.skip_rows + 1 + rows) {
+        text.push_str(&line?);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let layout = layout(&args)?;
+    let rows = args.flag_rows.unwrap_or(10);
+    let importer = importer(&args, &config(&args)?, inputs(&args)?)?.with_limit(rows);
+    let csv = Csv::new(vec![], false);
+
+    for file in inputs(&args)? {
+        println!("{}", file);
+        let layout = match importer.layout_for(&file, &layout) {
+            Some(layout) => layout,
+            None => {
+                println!("  No layout rule matches.");
+                continue;
+            }
+        };
+        let inspection = Inspection::default();
+        let format = layout.format(&file);
+        if !matches!(format, Format::Csv | Format::Fixed) {
+            println!("  Series: {}, format: {:?}", layout.series, format);
+            importer.import_file(&file, layout, &inspection)?;
+            match format {
+                Format::Json => report(&Json::named(layout), &inspection),
+                _ => report(layout, &inspection),
+            }
+            continue;
+        }
+        println!("  Series: {}, header row: {}", layout.series, layout.skip_rows + 1);
+
+        let text = head(&csv, &file, layout, rows)?;
+        let header = match csv.header(text.as_bytes(), layout) {
+            Ok(h) => h,
+            Err(e) => {
+                println!("  {}", e);
+                continue;
+            }
+        };
+        println!("  Columns:");
+        let columns: Vec<Vec<String>> = header
+            .iter()
+            .enumerate()
+            .map(|(i, c)| vec![format!("{}", i + 1), c.clone(), role(layout, c)])
+            .collect();
+        table(&columns);
+
+        let legends = Legends::new(layout)?;
+        csv.import_reader(&file, text.as_bytes(), layout, &legends.sink(&inspection))?;
+        report(layout, &inspection);
+    }
+    Ok(())
+}
+
+/// Print the points and errors found in some file.
+fn report(layout: &Layout, inspection: &Inspection) {
+    let found = inspection.points.borrow();
+    println!("  Points:");
+    table(&points(layout, &found));
+    println!("  Line protocol:");
+    for p in found.iter() {
+        println!("    {}", p.line());
+    }
+
+    let errors = inspection.errors.borrow();
+    if !errors.is_empty() {
+        println!("  Errors:");
+        for e in errors.iter() {
+            println!("    {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::DateTime;
+    use client::FieldValue;
+
+    #[test]
+    fn test_points_table() {
+        let layout = Layout {
+            tags: vec!["Status".into(), "Meter".into()],
+            ..Layout::default()
+        };
+        let msg = Message::new(
+            "series".into(),
+            DateTime::from_timestamp(0, 0).unwrap(),
+            ("data".into(), FieldValue::Integer(1)),
+            vec![("Status".into(), "220".into())],
+        );
+
+        let rows = points(&layout, &[msg]);
+        assert_eq!(rows[0], vec!["time", "data", "Status", "Meter"]);
+        assert_eq!(rows[1], vec!["1970-01-01T00:00:00+00:00", "1", "220", ""]);
+        assert_eq!(role(&layout, "timestamp"), "time (%F %H:%M:%S)");
+        assert_eq!(role(&layout, "data"), "measure (string)");
+        assert_eq!(role(&layout, "Meter"), "tag");
+        assert_eq!(role(&layout, "other"), "-");
+    }
+}
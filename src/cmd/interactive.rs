@@ -0,0 +1,31 @@
+//! Insert points by hand.
+use super::*;
+use mapper::{Interactive, Mapper};
+
+pub const USAGE: &str = concat!(
+    "
+Read points from stdin and insert them into influxdb.
+
+Usage:
+  x-influx interactive [options]
+  x-influx interactive (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+  -j, --journal FILE   Append messages which failed to write to FILE.
+                       See `x-influx replay`.
+",
+    connection_options!(),
+    layout_options!()
+);
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let layout = layout(&args)?;
+    let client = client(&args, &config(&args)?)?;
+    let mapper = Interactive {};
+    if let Err(e) = mapper.import(&layout, &client) {
+        error!(format!("Import failed {}", e));
+    }
+    shutdown(client)
+}
@@ -0,0 +1,27 @@
+//! Store the layout options in a file.
+use super::*;
+
+pub const USAGE: &str = concat!(
+    "
+Write the layout options to a file which can be read with --layout.
+
+Usage:
+  x-influx layout save [options] <layout-file>
+  x-influx layout (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+",
+    layout_options!(),
+    format_options!(),
+    csv_options!()
+);
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let layout = layout(&args)?;
+    let file = args.arg_layout_file.unwrap_or_default();
+    layout.save(&file)?;
+    info!(format!("Layout written to {}", file));
+    Ok(())
+}
@@ -0,0 +1,612 @@
+//! The subcommands of x-influx.
+//!
+//! Every command has its own usage and help text
+//! which are parsed into the shared `Args`.
+//! Options not known to every command are optional.
+use std::path::Path;
+
+use docopt::Docopt;
+use client::InfluxClient;
+use config::{Config, Connection, Profile};
+use error::{ConvertError, ConvertResult};
+use mapper::{Column, FieldType, Format, Importer, Inputs, Layout, Order};
+
+/// Options to connect to influxdb.
+macro_rules! connection_options {
+    () => {
+        "
+Connection options:
+  -c, --config FILE    Read server profiles from FILE instead of
+                       ~/.config/x-influx/config.toml.
+  --profile NAME       Use the server profile NAME from the config file.
+  -u, --user USER      Username for influxdb (default: test).
+  -p, --password PASS  Password for influxdb (default: empty).
+                       Prefer X_INFLUX_PASSWORD or --ask-password.
+  --ask-password       Prompt for the influxdb password.
+  -d, --database DB    Influx database (default: test).
+  -s, --server SRV     The influxdb server for import
+                       (default: http://localhost:8086).
+
+  Unset connection options are read from the X_INFLUX_SERVER,
+  X_INFLUX_USER, X_INFLUX_PASSWORD and X_INFLUX_DATABASE variables,
+  then from the selected profile.
+"
+    };
+}
+
+/// Options to describe the layout of the data.
+macro_rules! layout_options {
+    () => {
+        "
+Layout options:
+  -l, --layout FILE    Read the layout from FILE. The options below
+                       override the values of the layout file.
+  --input FMT          Format of the files, csv, fixed, json, sqlite,
+                       log, line for line protocol, sheet for xlsx and
+                       ods workbooks, mscons, parquet, arrow or
+                       prometheus (default: by file extension,
+                       else csv).
+  -S, --series VAL     Name of the measurement series (default: series).
+  -m, --measure VAL    Name of the measurement value (default: data).
+  -t, --tags VAL       Comma separated list of tags associated to a value.
+  -T, --time VAL       Name of the timestamp column (default: timestamp).
+  -f, --format FMT     The timestamp format (default: %F %H:%M:%S).
+                       See https://docs.rs/chrono/0.4.0/chrono/format/strftime/index.html
+  --timezone TZ        Timezone of the timestamps, e.g. Europe/Berlin
+                       (default: UTC).
+  --types VAL          Comma separated list of column:type pairs with
+                       type string, integer, float or boolean
+                       (default: string).
+  --locale LOC         Read numbers with the separators of a locale,
+                       e.g. de for 1.234,5 (default: en).
+  --decimal-sep SEP    Decimal separator of numbers (default: .).
+  --thousands-sep SEP  Thousands separator of numbers (default: none).
+"
+    };
+}
+
+/// Options of single formats, they override the table
+/// of the format in the layout file.
+macro_rules! format_options {
+    () => {
+        "
+Format options:
+  --query SQL          Query of sqlite files, :last is bound to the
+                       greatest time imported before.
+  --pattern PAT        Regex with named captures for the lines of log
+                       files, e.g. %{NGINX} or %{SYSLOG}.
+  --report-unmatched   Report the log lines which don't match.
+  --rename VAL         Comma separated list of old=new measurement names
+                       of line protocol files, *=new renames all.
+  --add-tags VAL       Comma separated list of key=value tags added to
+                       the points of line protocol files.
+  --sheet NAME         Sheet of workbooks, * reads all sheets
+                       (default: the first sheet).
+  --sheet-tag TAG      Add the name of the sheet as tag TAG.
+  --metric-fields      Write prometheus metrics as fields of the series.
+  --default-time TIME  Time of prometheus samples without timestamp
+                       (default: modification time of the file).
+"
+    };
+}
+
+/// Options to read csv files.
+macro_rules! csv_options {
+    () => {
+        "
+Csv options:
+  -D, --delimiter DEL  Use another csv delimiter (default: ,).
+  --skip-rows NUM      Remove first NUM lines from file (default: 0).
+  --widths VAL         Comma separated widths of the columns of fixed
+                       width files, implies --input fixed.
+  --encoding ENC       Encoding of the files, e.g. utf-8 or windows-1252
+                       (default: auto). Byte order marks are removed.
+
+  Layout rules of the config file select the layout of each
+  file by its name unless --layout or layout options are given.
+"
+    };
+}
+
+/// Options to find the input files.
+macro_rules! file_options {
+    () => {
+        "
+File options:
+  -r, --recursive      Read the files of subdirectories as well.
+  --include GLOB       Comma separated globs, only files in directories
+                       or found by globs which match one are read.
+  --exclude GLOB       Comma separated globs of files to skip.
+  --order ORD          Read the files of a directory or glob ordered by
+                       name or mtime (default: name).
+
+  Files can be given as directories or quoted globs like
+  'archive/**/*.csv' which are expanded without the shell.
+"
+    };
+}
+
+mod export;
+mod import;
+mod infer;
+mod inspect;
+mod interactive;
+mod layout;
+mod ping;
+mod replay;
+mod validate;
+mod watch;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Args {
+    pub flag_verbose: bool,
+    pub flag_config: Option<String>,
+    pub flag_profile: Option<String>,
+    pub flag_user: Option<String>,
+    pub flag_password: Option<String>,
+    pub flag_ask_password: Option<bool>,
+    pub flag_database: Option<String>,
+    pub flag_server: Option<String>,
+    pub flag_journal: Option<String>,
+    pub flag_follow: Option<bool>,
+    pub flag_state: Option<String>,
+    pub flag_resume: Option<bool>,
+    pub flag_layout: Option<String>,
+    pub flag_input: Option<String>,
+    pub flag_query: Option<String>,
+    pub flag_pattern: Option<String>,
+    pub flag_report_unmatched: Option<bool>,
+    pub flag_rename: Option<String>,
+    pub flag_add_tags: Option<String>,
+    pub flag_sheet: Option<String>,
+    pub flag_sheet_tag: Option<String>,
+    pub flag_metric_fields: Option<bool>,
+    pub flag_default_time: Option<String>,
+    pub flag_series: Option<String>,
+    pub flag_measure: Option<String>,
+    pub flag_tags: Option<String>,
+    pub flag_time: Option<String>,
+    pub flag_format: Option<String>,
+    pub flag_timezone: Option<String>,
+    pub flag_types: Option<String>,
+    pub flag_locale: Option<String>,
+    pub flag_decimal_sep: Option<char>,
+    pub flag_thousands_sep: Option<char>,
+    pub flag_delimiter: Option<char>,
+    pub flag_skip_rows: Option<usize>,
+    pub flag_widths: Option<String>,
+    pub flag_encoding: Option<String>,
+    pub flag_recursive: Option<bool>,
+    pub flag_include: Option<String>,
+    pub flag_exclude: Option<String>,
+    pub flag_order: Option<String>,
+    pub flag_interval: Option<u64>,
+    pub flag_settle: Option<u64>,
+    pub flag_output: Option<String>,
+    pub flag_from: Option<String>,
+    pub flag_to: Option<String>,
+    pub flag_rows: Option<usize>,
+    pub arg_file: Option<Vec<String>>,
+    pub arg_series: Option<String>,
+    pub arg_layout_file: Option<String>,
+    pub arg_dir: Option<String>,
+}
+
+impl Args {
+    /// The arguments with the password hidden, e.g. to log them.
+    pub fn redacted(&self) -> Args {
+        Args {
+            flag_password: self.flag_password.as_ref().map(|_| "***".into()),
+            ..self.clone()
+        }
+    }
+
+    /// The layout options given on the command line.
+    pub fn layout_options(&self) -> Vec<&'static str> {
+        let given = [
+            ("--input", self.flag_input.is_some()),
+            ("--query", self.flag_query.is_some()),
+            ("--pattern", self.flag_pattern.is_some()),
+            ("--report-unmatched", self.flag_report_unmatched == Some(true)),
+            ("--rename", self.flag_rename.is_some()),
+            ("--add-tags", self.flag_add_tags.is_some()),
+            ("--sheet", self.flag_sheet.is_some()),
+            ("--sheet-tag", self.flag_sheet_tag.is_some()),
+            ("--metric-fields", self.flag_metric_fields == Some(true)),
+            ("--default-time", self.flag_default_time.is_some()),
+            ("--series", self.flag_series.is_some()),
+            ("--measure", self.flag_measure.is_some()),
+            ("--tags", self.flag_tags.is_some()),
+            ("--time", self.flag_time.is_some()),
+            ("--format", self.flag_format.is_some()),
+            ("--timezone", self.flag_timezone.is_some()),
+            ("--types", self.flag_types.is_some()),
+            ("--locale", self.flag_locale.is_some()),
+            ("--decimal-sep", self.flag_decimal_sep.is_some()),
+            ("--thousands-sep", self.flag_thousands_sep.is_some()),
+            ("--delimiter", self.flag_delimiter.is_some()),
+            ("--skip-rows", self.flag_skip_rows.is_some()),
+            ("--widths", self.flag_widths.is_some()),
+            ("--encoding", self.flag_encoding.is_some()),
+        ];
+        given.iter().filter(|&&(_, set)| set).map(|&(flag, _)| flag).collect()
+    }
+}
+
+/// A subcommand with its usage and implementation.
+pub struct Command {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub execute: fn(Args) -> ConvertResult<()>,
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "import",
+        usage: import::USAGE,
+        execute: import::execute,
+    },
+    Command {
+        name: "interactive",
+        usage: interactive::USAGE,
+        execute: interactive::execute,
+    },
+    Command {
+        name: "inspect",
+        usage: inspect::USAGE,
+        execute: inspect::execute,
+    },
+    Command {
+        name: "validate",
+        usage: validate::USAGE,
+        execute: validate::execute,
+    },
+    Command {
+        name: "watch",
+        usage: watch::USAGE,
+        execute: watch::execute,
+    },
+    Command {
+        name: "export",
+        usage: export::USAGE,
+        execute: export::execute,
+    },
+    Command {
+        name: "replay",
+        usage: replay::USAGE,
+        execute: replay::execute,
+    },
+    Command {
+        name: "ping",
+        usage: ping::USAGE,
+        execute: ping::execute,
+    },
+    Command {
+        name: "infer",
+        usage: infer::USAGE,
+        execute: infer::execute,
+    },
+    Command {
+        name: "layout",
+        usage: layout::USAGE,
+        execute: layout::execute,
+    },
+];
+
+/// Return the command by its name. The former modes
+/// `i` and `b` are accepted as aliases.
+pub fn find(name: &str) -> Option<&'static Command> {
+    let name = match name {
+        "i" => "interactive",
+        "b" => "import",
+        n => n,
+    };
+    COMMANDS.iter().find(|c| c.name == name)
+}
+
+/// Return the command named by the program arguments. An alias is
+/// replaced by the name of its command, so its usage can be parsed.
+/// Other arguments are passed to `import` to keep the plain csv
+/// import working without command, as are files named like a
+/// command, e.g. `x-influx b` imports the file `b` if it exists.
+pub fn select(argv: &mut Vec<String>) -> &'static Command {
+    let command = argv
+        .get(1)
+        .filter(|a| !Path::new(a).exists())
+        .and_then(|a| find(a));
+    match command {
+        Some(c) => {
+            argv[1] = c.name.into();
+            c
+        }
+        None => {
+            argv.insert(1, "import".into());
+            find("import").unwrap()
+        }
+    }
+}
+
+/// Parse the program arguments with the usage of the command.
+pub fn parse(command: &Command, argv: Vec<String>) -> Args {
+    Docopt::new(command.usage)
+        .and_then(|d| d.argv(argv).deserialize())
+        .unwrap_or_else(|e| e.exit())
+}
+
+/// Read the given config file or the default one.
+pub fn config(args: &Args) -> ConvertResult<Config> {
+    Config::load_or_default(args.flag_config.as_deref())
+}
+
+/// Merge the connection settings from the command line,
+/// the environment and the config file profile.
+pub fn connection(args: &Args, config: &Config) -> ConvertResult<Connection> {
+    let profile = config.profile(args.flag_profile.as_deref())?;
+    let cli = Profile {
+        server: args.flag_server.clone(),
+        user: args.flag_user.clone(),
+        password: args.flag_password.clone(),
+        password_env: None,
+        database: args.flag_database.clone(),
+    };
+    cli.or(Profile::from_env())
+        .or(profile)
+        .resolve(args.flag_ask_password.unwrap_or(false))
+}
+
+/// Start the background influx client.
+pub fn client(args: &Args, config: &Config) -> ConvertResult<InfluxClient> {
+    let con = connection(args, config)?;
+    let client = InfluxClient::new(
+        con.server,
+        con.user,
+        con.password,
+        con.database,
+        args.flag_journal.clone(),
+    )?;
+    info!("Background influx client up and running.");
+    Ok(client)
+}
+
+/// Wait until the client has written all messages.
+pub fn shutdown(client: InfluxClient) -> ConvertResult<()> {
+    client.join()?;
+    info!("Graceful shutdown.");
+    Ok(())
+}
+
+/// Split some comma separated list of key=value pairs.
+fn pairs(list: &str) -> ConvertResult<Vec<(String, String)>> {
+    list.split(',')
+        .filter(|e| !e.is_empty())
+        .map(|pair| {
+            pair.split_once('=')
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .ok_or_else(|| ConvertError::Config(format!("Expected key=value but got {}", pair)))
+        })
+        .collect()
+}
+
+/// Read the layout file if given and override it with the layout options.
+pub fn layout(args: &Args) -> ConvertResult<Layout> {
+    let mut layout = match args.flag_layout {
+        Some(ref f) => Layout::load(f)?,
+        None => Layout::default(),
+    };
+
+    if let Some(ref i) = args.flag_input {
+        layout.format = Some(Format::from_name(i)?);
+    }
+    if let Some(ref q) = args.flag_query {
+        layout.sqlite.query = Some(q.clone());
+    }
+    if let Some(ref p) = args.flag_pattern {
+        layout.log.pattern = Some(p.clone());
+    }
+    if args.flag_report_unmatched == Some(true) {
+        layout.log.report_unmatched = true;
+    }
+    if let Some(ref r) = args.flag_rename {
+        layout.line.rename.extend(pairs(r)?);
+    }
+    if let Some(ref t) = args.flag_add_tags {
+        layout.line.add_tags.extend(pairs(t)?);
+    }
+    if let Some(ref s) = args.flag_sheet {
+        layout.sheet.name = Some(s.clone());
+    }
+    if let Some(ref t) = args.flag_sheet_tag {
+        layout.sheet.tag = Some(t.clone());
+    }
+    if args.flag_metric_fields == Some(true) {
+        layout.prometheus.metric_fields = true;
+    }
+    if let Some(ref t) = args.flag_default_time {
+        layout.prometheus.default_time = Some(t.clone());
+    }
+    if let Some(ref s) = args.flag_series {
+        layout.series = s.clone();
+    }
+    if let Some(ref m) = args.flag_measure {
+        layout.measure = m.clone();
+    }
+    if let Some(ref t) = args.flag_tags {
+        layout.tags = t.split(',')
+            .filter(|e| !e.is_empty())
+            .map(|e| e.to_owned())
+            .collect();
+    }
+    if let Some(ref t) = args.flag_time {
+        layout.time = t.clone();
+    }
+    if let Some(ref f) = args.flag_format {
+        layout.tformat = f.clone();
+    }
+    if let Some(ref tz) = args.flag_timezone {
+        layout.timezone = Some(tz.clone());
+    }
+    if let Some(ref types) = args.flag_types {
+        for pair in types.split(',').filter(|e| !e.is_empty()) {
+            let (column, name) = pair.rsplit_once(':').ok_or_else(|| {
+                ConvertError::Config(format!("Expected column:type but got {}", pair))
+            })?;
+            layout
+                .types
+                .insert(column.to_owned(), FieldType::from_name(name)?);
+        }
+    }
+    if let Some(ref l) = args.flag_locale {
+        layout.set_locale(l)?;
+    }
+    if let Some(d) = args.flag_decimal_sep {
+        layout.decimal_sep = d;
+    }
+    if let Some(t) = args.flag_thousands_sep {
+        layout.thousands_sep = Some(t);
+    }
+    if let Some(d) = args.flag_delimiter {
+        layout.delimiter = d;
+    }
+    if let Some(n) = args.flag_skip_rows {
+        layout.skip_rows = n;
+    }
+    if let Some(ref w) = args.flag_widths {
+        layout.format = Some(Format::Fixed);
+        layout.fixed.columns = w
+            .split(',')
+            .filter(|e| !e.is_empty())
+            .map(|e| {
+                e.trim().parse().map(Column::width).map_err(|_| {
+                    ConvertError::Config(format!("Expected a width but got {}", e))
+                })
+            })
+            .collect::<ConvertResult<_>>()?;
+    }
+    if let Some(ref e) = args.flag_encoding {
+        layout.encoding = Some(e.clone());
+    }
+
+    layout.check()?;
+    debug!(format!("{:?}", layout));
+    Ok(layout)
+}
+
+/// The importer of the given files. A layout file or layout
+/// options take precedence over the layout rules of the config.
+pub fn importer(args: &Args, config: &Config, files: Vec<String>) -> ConvertResult<Importer> {
+    let options = args.layout_options();
+    let rules = match args.flag_layout.is_some() || !options.is_empty() {
+        true => {
+            debug!(format!("Ignoring the layout rules because of {:?}", options));
+            vec![]
+        }
+        false => config.rules()?,
+    };
+    Ok(Importer::new(files).with_rules(rules))
+}
+
+/// Split some comma separated list of globs.
+pub fn globs(list: &Option<String>) -> Vec<String> {
+    list.as_ref()
+        .map(|g| g.split(',').filter(|e| !e.is_empty()).map(|e| e.to_owned()).collect())
+        .unwrap_or_default()
+}
+
+/// The files given on the command line with directories
+/// and globs expanded by the file options.
+pub fn inputs(args: &Args) -> ConvertResult<Vec<String>> {
+    let order = match args.flag_order {
+        Some(ref o) => Order::from_name(o)?,
+        None => Order::Name,
+    };
+    Inputs::default()
+        .with_recursive(args.flag_recursive.unwrap_or(false))
+        .with_include(&globs(&args.flag_include))?
+        .with_exclude(&globs(&args.flag_exclude))?
+        .with_order(order)
+        .expand(&files(args))
+}
+
+/// The files given on the command line.
+pub fn files(args: &Args) -> Vec<String> {
+    args.arg_file.clone().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    fn argv(args: &str) -> Vec<String> {
+        args.split_whitespace().map(|a| a.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_redacted() {
+        let args = parse(find("import").unwrap(), argv("x-influx import -p secret a.csv"));
+        assert_eq!(args.flag_password.as_deref(), Some("secret"));
+        let logged = format!("{:?}", args.redacted());
+        assert!(!logged.contains("secret"));
+        assert_eq!(args.redacted().arg_file, args.arg_file);
+    }
+
+    #[test]
+    fn test_rules_or_options() {
+        let config = Config::parse(
+            "[[rules]]\nglob = \"pv_*.csv\"\nlayout = { series = \"pv\" }",
+        ).unwrap();
+        let files = vec!["pv_1.csv".to_string(), "other.csv".to_string()];
+        let command = find("import").unwrap();
+
+        let args = parse(command, argv("x-influx import pv_1.csv other.csv"));
+        let cli = layout(&args).unwrap();
+        let mapper = importer(&args, &config, files.clone()).unwrap();
+        assert_eq!(mapper.layout_for(&files[0], &cli).unwrap().series, "pv");
+        assert_eq!(mapper.layout_for(&files[1], &cli), None);
+
+        // layout options win over the rules
+        let args = parse(command, argv("x-influx import -m kWh pv_1.csv"));
+        let cli = layout(&args).unwrap();
+        let mapper = importer(&args, &config, files.clone()).unwrap();
+        assert_eq!(mapper.layout_for(&files[0], &cli).unwrap().measure, "kWh");
+        assert_eq!(mapper.layout_for(&files[0], &cli).unwrap().series, "series");
+
+        // even if the option has its default value
+        let args = parse(command, argv("x-influx import -D , pv_1.csv"));
+        assert_eq!(args.layout_options(), vec!["--delimiter"]);
+        let cli = layout(&args).unwrap();
+        assert_eq!(cli, Layout::default());
+        let mapper = importer(&args, &config, files.clone()).unwrap();
+        assert_eq!(mapper.layout_for(&files[0], &cli), Some(&cli));
+    }
+
+    #[test]
+    fn test_select() {
+        let mut legacy = argv("x-influx b -v --skip-rows 10 -D ; assets/test.csv");
+        let command = select(&mut legacy);
+        assert_eq!(command.name, "import");
+        let args = parse(command, legacy);
+        assert!(args.flag_verbose);
+        assert_eq!(args.flag_skip_rows, Some(10));
+        assert_eq!(args.arg_file, Some(vec!["assets/test.csv".into()]));
+
+        let mut interactive = argv("x-influx i -s http://db:8086");
+        assert_eq!(select(&mut interactive).name, "interactive");
+        assert_eq!(interactive[1], "interactive");
+
+        // globs and missing files are imported and reported there
+        let mut plain = argv("x-influx archive/*.csv");
+        assert_eq!(select(&mut plain).name, "import");
+        let args = parse(find("import").unwrap(), plain);
+        assert_eq!(args.arg_file, Some(vec!["archive/*.csv".into()]));
+
+        // existing files named like a command are imported
+        fs::write("b", "").unwrap();
+        let mut file = argv("x-influx b");
+        let command = select(&mut file);
+        fs::remove_file("b").unwrap();
+        assert_eq!(command.name, "import");
+        assert_eq!(file, argv("x-influx import b"));
+    }
+}
@@ -0,0 +1,36 @@
+//! Check the connection to influxdb.
+use super::*;
+use client;
+
+pub const USAGE: &str = concat!(
+    "
+Check that the influxdb server is reachable and the database exists.
+
+Usage:
+  x-influx ping [options]
+  x-influx ping (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+",
+    connection_options!()
+);
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let con = connection(&args, &config(&args)?)?;
+    let res = client::query(&con, "SHOW DATABASES")?;
+    let found = res.pointer("/results/0/series/0/values")
+        .and_then(|v| v.as_array())
+        .is_some_and(|dbs| {
+            dbs.iter().any(|db| db[0].as_str() == Some(con.database.as_str()))
+        });
+
+    match found {
+        true => {
+            info!(format!("{} is up, database {} found.", con.server, con.database));
+            Ok(())
+        }
+        false => Err(ConvertError::NotFound(format!("Database {}", con.database))),
+    }
+}
@@ -0,0 +1,51 @@
+//! Send the messages of a journal again.
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use serde_json;
+use super::*;
+use client::Message;
+
+pub const USAGE: &str = concat!(
+    "
+Send the messages of journal files written with --journal again.
+
+Usage:
+  x-influx replay [options] <file>...
+  x-influx replay (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+  -j, --journal FILE   Append messages which failed again to FILE.
+",
+    connection_options!()
+);
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let files = files(&args);
+    if let Some(ref j) = args.flag_journal {
+        if files.iter().any(|f| Path::new(f) == Path::new(j)) {
+            return Err(ConvertError::Config(format!("Can't replay into the journal {}", j)));
+        }
+    }
+
+    let client = client(&args, &config(&args)?)?;
+    for file in &files {
+        let reader = File::open(file)
+            .map(BufReader::new)
+            .map_err(|e| ConvertError::Import(format!("Failed to open {}: {}", file, e)))?;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Message>(&line) {
+                Ok(msg) => client.send(msg)?,
+                Err(e) => error!(format!("{}:{}: {}", file, i + 1, e)),
+            }
+        }
+    }
+    shutdown(client)
+}
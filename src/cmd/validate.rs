@@ -0,0 +1,60 @@
+//! Check files against a layout without writing.
+use std::cell::Cell;
+
+use super::*;
+use client::Message;
+use mapper::{Mapper, Sink};
+
+pub const USAGE: &str = concat!(
+    "
+Convert csv files like `import` but without writing to influxdb.
+Every row which can't be converted is reported.
+
+Usage:
+  x-influx validate [options] <file>...
+  x-influx validate (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+  -c, --config FILE    Read layout rules from FILE instead of
+                       ~/.config/x-influx/config.toml.
+",
+    layout_options!(),
+    format_options!(),
+    csv_options!(),
+    file_options!()
+);
+
+/// Count the converted and rejected rows.
+#[derive(Debug, Default)]
+struct Validation {
+    points: Cell<usize>,
+    rejected: Cell<usize>,
+}
+
+impl Sink for Validation {
+    fn send(&self, _: Message) -> ConvertResult<()> {
+        self.points.set(self.points.get() + 1);
+        Ok(())
+    }
+
+    fn reject(&self, source: &str, row: usize, err: ConvertError) {
+        self.rejected.set(self.rejected.get() + 1);
+        error!(format!("{}:{}: {}", source, row, err));
+    }
+}
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let layout = layout(&args)?;
+    let importer = importer(&args, &config(&args)?, inputs(&args)?)?;
+    let validation = Validation::default();
+    importer.import(&layout, &validation)?;
+
+    let (points, rejected) = (validation.points.get(), validation.rejected.get());
+    info!(format!("{} points, {} rejected rows", points, rejected));
+    match rejected {
+        0 => Ok(()),
+        n => Err(ConvertError::Import(format!("{} rows failed validation", n))),
+    }
+}
@@ -0,0 +1,261 @@
+//! Import files dropped into an inbox directory.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use ctrlc;
+use super::*;
+use client::Message;
+use mapper::Sink;
+
+pub const USAGE: &str = concat!(
+    "
+Watch a directory for new csv files and import them once they
+are fully written. Files are moved to done/ once influxdb wrote
+their points, files with errors or failed writes to failed/ next
+to a report of the errors. Runs until interrupted.
+
+Usage:
+  x-influx watch [options] <dir>
+  x-influx watch (-h | --help)
+
+Options:
+  -h, --help           Print this help message.
+  -v, --verbose        Enable verbose logging.
+  -j, --journal FILE   Append messages which failed to write to FILE.
+                       See `x-influx replay`.
+  --interval SECS      Seconds between two scans of the directory
+                       (default: 5).
+  --settle SECS        Seconds a file must be unchanged before it
+                       is imported (default: 10).
+  --include GLOB       Comma separated globs, only matching files
+                       are imported.
+  --exclude GLOB       Comma separated globs of files to ignore,
+                       e.g. *.part.
+",
+    connection_options!(),
+    layout_options!(),
+    format_options!(),
+    csv_options!()
+);
+
+/// Size and modification time of the files seen in the inbox.
+#[derive(Debug, Default)]
+struct Inbox {
+    seen: HashMap<String, (u64, SystemTime)>,
+}
+
+impl Inbox {
+    /// Return the files which didn't change since the last scan
+    /// and were not modified for the settle time.
+    fn ready(&mut self, files: Vec<String>, settle: Duration) -> Vec<String> {
+        let mut ready = vec![];
+        let mut seen = HashMap::new();
+        for file in files {
+            let meta = match fs::metadata(&file).and_then(|m| m.modified().map(|t| (m.len(), t))) {
+                Ok(m) => m,
+                Err(_) => continue,
+            };
+            let settled = meta.1.elapsed().map(|e| e >= settle).unwrap_or(false);
+            if self.seen.get(&file) == Some(&meta) && settled {
+                ready.push(file);
+            } else {
+                seen.insert(file, meta);
+            }
+        }
+        self.seen = seen;
+        ready
+    }
+}
+
+/// Pass the points to the client and collect the errors of a file.
+struct Report<'a> {
+    client: &'a InfluxClient,
+    points: Cell<usize>,
+    errors: RefCell<Vec<String>>,
+}
+
+impl<'a> Sink for Report<'a> {
+    fn send(&self, msg: Message) -> ConvertResult<()> {
+        self.points.set(self.points.get() + 1);
+        self.client.send(msg)
+    }
+
+    fn reject(&self, source: &str, row: usize, err: ConvertError) {
+        self.errors
+            .borrow_mut()
+            .push(format!("{}:{}: {}", source, row, err));
+    }
+}
+
+/// Move the file into the directory without overwriting other files.
+/// The errors are written next to it if there are some.
+fn archive(file: &str, dir: &Path, errors: &[String]) -> ConvertResult<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = Path::new(file)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut target = dir.join(&name);
+    let mut i = 1;
+    while target.exists() {
+        target = dir.join(format!("{}.{}", name, i));
+        i += 1;
+    }
+
+    fs::rename(file, &target)?;
+    if !errors.is_empty() {
+        let report = format!("{}.errors.txt", target.display());
+        fs::write(report, errors.join("\n") + "\n")?;
+    }
+    Ok(target)
+}
+
+/// Import a single file and return its errors. The file is only
+/// imported once the client wrote all its points.
+fn import(
+    importer: &Importer,
+    layout: &Layout,
+    file: &str,
+    client: &InfluxClient,
+) -> Vec<String> {
+    let layout = match importer.layout_for(file, layout) {
+        Some(layout) => layout,
+        None => return vec![format!("{}: No layout rule matches", file)],
+    };
+    let report = Report {
+        client,
+        points: Cell::new(0),
+        errors: RefCell::new(vec![]),
+    };
+    if let Err(e) = importer.import_file(file, layout, &report) {
+        report.errors.borrow_mut().push(format!("{}: {}", file, e));
+    }
+    let mut errors = report.errors.into_inner();
+    errors.extend(client.flush().into_iter().map(|e| format!("{}: {}", file, e)));
+    info!(format!("{}: {} points", file, report.points.get()));
+    errors
+}
+
+pub fn execute(args: Args) -> ConvertResult<()> {
+    let layout = layout(&args)?;
+    let config = config(&args)?;
+    let importer = importer(&args, &config, vec![])?;
+    let dir = args.arg_dir.clone().unwrap_or_default();
+    let inputs = Inputs::default()
+        .with_include(&globs(&args.flag_include))?
+        .with_exclude(&globs(&args.flag_exclude))?;
+    let interval = Duration::from_secs(args.flag_interval.unwrap_or(5));
+    let settle = Duration::from_secs(args.flag_settle.unwrap_or(10));
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))
+        .map_err(|e| ConvertError::Config(format!("Failed to handle signals: {}", e)))?;
+
+    let client = client(&args, &config)?;
+    let (done, failed) = (Path::new(&dir).join("done"), Path::new(&dir).join("failed"));
+    let mut inbox = Inbox::default();
+    info!(format!("Watching {} until interrupted.", dir));
+
+    while !stop.load(Ordering::SeqCst) {
+        let files = match inputs.expand(slice::from_ref(&dir)) {
+            Ok(f) => f,
+            Err(e) => {
+                error!(format!("{}", e));
+                vec![]
+            }
+        };
+
+        for file in inbox.ready(files, settle) {
+            let errors = import(&importer, &layout, &file, &client);
+            let target = match errors.is_empty() {
+                true => &done,
+                false => &failed,
+            };
+            match archive(&file, target, &errors) {
+                Ok(t) => info!(format!("Moved {} to {}", file, t.display())),
+                Err(e) => error!(format!("Failed to move {}: {}", file, e)),
+            }
+        }
+
+        // sleep in small steps to react to signals
+        let start = SystemTime::now();
+        while !stop.load(Ordering::SeqCst)
+            && start.elapsed().map(|e| e < interval).unwrap_or(false)
+        {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+    shutdown(client)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_ready() {
+        let dir = env::temp_dir().join(format!("x-influx-test-{}-watch-ready", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.csv").to_string_lossy().into_owned();
+        fs::write(&file, "timestamp").unwrap();
+
+        let mut inbox = Inbox::default();
+        let none = Duration::from_secs(0);
+        assert!(inbox.ready(vec![file.clone()], none).is_empty());
+        assert_eq!(inbox.ready(vec![file.clone()], none), vec![file.clone()]);
+
+        fs::write(&file, "timestamp,data").unwrap();
+        assert!(inbox.ready(vec![file.clone()], none).is_empty());
+        let hour = Duration::from_secs(3600);
+        assert!(inbox.ready(vec![file.clone()], hour).is_empty());
+    }
+
+    #[test]
+    fn test_archive() {
+        let dir = env::temp_dir().join(format!("x-influx-test-{}-watch-archive", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.csv");
+        let failed = dir.join("failed");
+
+        fs::write(&file, "").unwrap();
+        let first = archive(file.to_str().unwrap(), &failed, &["a.csv:2: broken".into()]).unwrap();
+        assert_eq!(first, failed.join("a.csv"));
+        assert!(failed.join("a.csv.errors.txt").exists());
+
+        fs::write(&file, "").unwrap();
+        let second = archive(file.to_str().unwrap(), &failed, &[]).unwrap();
+        assert_eq!(second, failed.join("a.csv.1"));
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn test_failed_write() {
+        // nothing listens on the discard port
+        let client = InfluxClient::new(
+            "http://127.0.0.1:9".into(),
+            "test".into(),
+            "".into(),
+            "test".into(),
+            None,
+        ).unwrap();
+        let layout = Layout::load("assets/test.toml").unwrap();
+        let importer = Importer::default();
+        let errors = import(&importer, &layout, "assets/test.csv", &client);
+        assert_eq!(errors.len(), 4);
+        assert!(errors[0].starts_with("assets/test.csv: Failed to write to influxdb."));
+        assert!(client.flush().is_empty());
+        client.join().unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+//! Named server profiles read from a toml configuration file.
+//!
+//! The connection settings are merged from the command line,
+//! the environment and the configuration file in this order
+//! of precedence. Unset values fall back to the defaults.
+//!
+//! ```toml
+//! default_profile = "prod"
+//!
+//! [profiles.prod]
+//! server = "https://influx.example.com:8086"
+//! user = "importer"
+//! database = "meters"
+//! password_env = "PROD_INFLUX_PASSWORD"
+//!
+//! [[rules]]
+//! glob = "*_lastgang.csv"
+//! layout = "layouts/lastgang.toml"
+//!
+//! [[rules]]
+//! regex = "^wetter_.*\\.txt$"
+//! layout = { series = "weather", delimiter = ";" }
+//! ```
+//!
+//! Rules select the layout of an imported file by its name.
+//! Relative layout paths are read from the config file's directory.
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use rpassword;
+use toml;
+use error::{ConvertError, ConvertResult};
+use mapper::{Layout, Rule};
+
+/// Environment variables which are read as a connection source.
+pub const ENV_SERVER: &str = "X_INFLUX_SERVER";
+pub const ENV_USER: &str = "X_INFLUX_USER";
+pub const ENV_PASSWORD: &str = "X_INFLUX_PASSWORD";
+pub const ENV_DATABASE: &str = "X_INFLUX_DATABASE";
+
+/// Connection settings for some influxdb server.
+/// Every field is optional so that different sources can be merged.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub server: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Name of an environment variable which holds the password.
+    pub password_env: Option<String>,
+    pub database: Option<String>,
+}
+
+impl Profile {
+    /// Read the connection settings from the `X_INFLUX_*` variables.
+    pub fn from_env() -> Profile {
+        Profile::from_vars(|k| env::var(k).ok())
+    }
+
+    /// Read the connection settings with some variable lookup.
+    fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Profile {
+        Profile {
+            server: var(ENV_SERVER),
+            user: var(ENV_USER),
+            password: var(ENV_PASSWORD),
+            password_env: None,
+            database: var(ENV_DATABASE),
+        }
+    }
+
+    /// Merge two profiles where the values of `self` take precedence.
+    pub fn or(self, other: Profile) -> Profile {
+        Profile {
+            server: self.server.or(other.server),
+            user: self.user.or(other.user),
+            password: self.password.or(other.password),
+            password_env: self.password_env.or(other.password_env),
+            database: self.database.or(other.database),
+        }
+    }
+
+    /// Fill in the defaults and return the final connection settings.
+    /// If no password is set it is either read from the environment
+    /// variable named by `password_env` or prompted if `ask` is set.
+    pub fn resolve(self, ask: bool) -> ConvertResult<Connection> {
+        let password = match (self.password, self.password_env) {
+            (Some(p), _) => p,
+            (None, Some(ref var)) if !ask => env::var(var).map_err(|_| {
+                ConvertError::Config(format!("Password variable {} is not set", var))
+            })?,
+            (None, _) if ask => rpassword::prompt_password("Password: ")?,
+            (None, _) => String::new(),
+        };
+
+        Ok(Connection {
+            server: self.server
+                .unwrap_or_else(|| "http://localhost:8086".into()),
+            user: self.user.unwrap_or_else(|| "test".into()),
+            password,
+            database: self.database.unwrap_or_else(|| "test".into()),
+        })
+    }
+}
+
+/// The merged settings used to connect to influxdb.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection {
+    pub server: String,
+    pub user: String,
+    pub password: String,
+    pub database: String,
+}
+
+/// A layout given inline or as path to some layout file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum LayoutRef {
+    File(PathBuf),
+    Inline(Box<Layout>),
+}
+
+/// Select a layout for all files matching either
+/// the glob or the regex.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    pub glob: Option<String>,
+    pub regex: Option<String>,
+    pub layout: LayoutRef,
+}
+
+/// The configuration file with its named server profiles.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The profile used if none is given on the command line.
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+    /// Layout rules applied in the order of declaration.
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    /// The directory of the config file.
+    #[serde(skip)]
+    base: PathBuf,
+}
+
+impl Config {
+    /// Parse a configuration from its toml representation.
+    pub fn parse(s: &str) -> ConvertResult<Config> {
+        toml::from_str(s).map_err(|e| ConvertError::Config(format!("{}", e)))
+    }
+
+    /// Read the configuration file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> ConvertResult<Config> {
+        let path = path.as_ref();
+        let mut content = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut content))
+            .map_err(|e| {
+                ConvertError::Config(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+        let mut config = Config::parse(&content)?;
+        config.base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(config)
+    }
+
+    /// Read the given configuration file or the one from the
+    /// default location. A missing default file yields an empty config.
+    pub fn load_or_default(path: Option<&str>) -> ConvertResult<Config> {
+        match (path, Config::default_path()) {
+            (Some(p), _) => Config::load(p),
+            (None, Some(ref p)) if p.is_file() => Config::load(p),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/x-influx/config.toml` or
+    /// `~/.config/x-influx/config.toml` as fallback.
+    pub fn default_path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .map(|p| p.join("x-influx").join("config.toml"))
+    }
+
+    /// Return the named profile, the default profile if no name is
+    /// given or an empty profile if there is no default.
+    pub fn profile(&self, name: Option<&str>) -> ConvertResult<Profile> {
+        match name.or(self.default_profile.as_deref()) {
+            None => Ok(Profile::default()),
+            Some(n) => self.profiles
+                .get(n)
+                .cloned()
+                .ok_or_else(|| ConvertError::Config(format!("Profile {} not found", n))),
+        }
+    }
+
+    /// Read the referenced layouts and compile the layout rules.
+    pub fn rules(&self) -> ConvertResult<Vec<Rule>> {
+        self.rules
+            .iter()
+            .map(|r| {
+                let layout = match r.layout {
+                    LayoutRef::File(ref f) => Layout::load(self.base.join(f))?,
+                    LayoutRef::Inline(ref l) => (**l).clone().relative_to(&self.base),
+                };
+                match (&r.glob, &r.regex) {
+                    (Some(g), None) => Rule::glob(g, layout),
+                    (None, Some(re)) => Rule::regex(re, layout),
+                    _ => Err(ConvertError::Config(
+                        "A rule needs either a glob or a regex".into(),
+                    )),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = "
+default_profile = \"dev\"
+
+[profiles.dev]
+server = \"http://dev:8086\"
+database = \"devdb\"
+
+[profiles.prod]
+server = \"https://prod:8086\"
+user = \"importer\"
+password_env = \"PROD_PASS\"
+database = \"meters\"
+";
+
+    #[test]
+    fn test_select_profile() {
+        let config = Config::parse(CONFIG).unwrap();
+        let dev = config.profile(None).unwrap();
+        assert_eq!(dev.server, Some("http://dev:8086".into()));
+        let prod = config.profile(Some("prod")).unwrap();
+        assert_eq!(prod.user, Some("importer".into()));
+        assert!(config.profile(Some("stage")).is_err());
+        assert_eq!(
+            Config::default().profile(None).unwrap(),
+            Profile::default()
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let config = Config::parse(
+            "
+[[rules]]
+glob = \"*_lastgang.csv\"
+layout = \"test.toml\"
+
+[[rules]]
+regex = \"^wetter_.*\\\\.txt$\"
+layout = { series = \"weather\", delimiter = \";\", tags = [\"Status\"], legend = [
+    { column = \"Status\", tag = \"Status\", file = \"status.csv\" },
+] }
+",
+        ).unwrap();
+        assert!(config.rules().is_err());
+
+        let config = Config {
+            base: PathBuf::from("assets"),
+            ..config
+        };
+        let rules = config.rules().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].matches("drop/4711_lastgang.csv"));
+        assert_eq!(rules[0].layout.series, "meter");
+        assert!(rules[1].matches("wetter_berlin.txt"));
+        assert_eq!(rules[1].layout.delimiter, ';');
+        assert_eq!(rules[1].layout.legend[0].file.as_deref(), Some("assets/status.csv"));
+
+        let config = Config::parse("[[rules]]\nlayout = \"test.toml\"").unwrap();
+        assert!(config.rules().is_err());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(Config::parse("[profiles.dev]\nhost = \"x\"").is_err());
+        assert!(Config::load("assets/missing.toml").is_err());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let config = Config::parse(CONFIG).unwrap();
+        let cli = Profile {
+            database: Some("clidb".into()),
+            ..Profile::default()
+        };
+        let env = Profile::from_vars(|k| match k {
+            ENV_SERVER => Some("http://env:8086".into()),
+            ENV_DATABASE => Some("envdb".into()),
+            _ => None,
+        });
+
+        let con = cli.or(env)
+            .or(config.profile(None).unwrap())
+            .resolve(false)
+            .unwrap();
+        assert_eq!(
+            con,
+            Connection {
+                server: "http://env:8086".into(),
+                user: "test".into(),
+                password: "".into(),
+                database: "clidb".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_password_from_env() {
+        let profile = Profile {
+            password_env: Some("X_INFLUX_TEST_PASSWORD_UNSET".into()),
+            ..Profile::default()
+        };
+        assert!(profile.clone().resolve(false).is_err());
+
+        let profile = Profile {
+            password: Some("secret".into()),
+            ..profile
+        };
+        assert_eq!(profile.resolve(false).unwrap().password, "secret");
+    }
+}
@@ -4,6 +4,7 @@ use std::io;
 use std::any::Any;
 use std::marker::Send;
 use std::sync::mpsc;
+use chrono::Utc;
 use client::Message;
 
 /// A simple constant logger which
@@ -20,19 +21,23 @@ pub enum Level {
     Info,
 }
 
+/// The current time as prefix of log messages.
+#[doc(hidden)]
+pub fn now() -> String {
+    Utc::now().format("%F %T").to_string()
+}
+
 #[macro_export]
 macro_rules! info {
     ($e:expr) => {{
-        use chrono::Utc;
-        println!("{} Info: {:?}", Utc::now().format("%F %T"), $e);
+        println!("{} Info: {:?}", $crate::error::now(), $e);
     }}
 }
 
 #[macro_export]
 macro_rules! error {
     ($e:expr) => {{
-        use chrono::Utc;
-        println!("{} Error: {:?}", Utc::now().format("%F %T"), $e);
+        println!("{} Error: {:?}", $crate::error::now(), $e);
     }}
 }
 
@@ -41,10 +46,9 @@ macro_rules! error {
 #[macro_export]
 macro_rules! debug {
     ($e:expr) => {unsafe {
-        use chrono::Utc;
-        use error::{Level, LOGGER};
+        use $crate::error::{Level, LOGGER};
         if LOGGER.0 == Level::Debug {
-            println!("{} Debug: {:?}", Utc::now().format("%F %T"), $e);
+            println!("{} Debug: {:?}", $crate::error::now(), $e);
         }
     }}
 }
@@ -53,7 +57,7 @@ macro_rules! debug {
 #[macro_export]
 macro_rules! set_debug {
     () => {unsafe {
-        use error::{Level, LOGGER, Logger};
+        use $crate::error::{Level, LOGGER, Logger};
         LOGGER = &Logger(Level::Debug);
     }}
 }
@@ -74,6 +78,10 @@ pub enum ConvertError {
     Join(Box<Any + Send>),
     /// Sending to client failed
     Send(mpsc::SendError<Option<Message>>),
+    /// If the configuration is invalid or missing.
+    Config(String),
+    /// If some request to influxdb failed.
+    Client(String),
 }
 
 impl fmt::Display for ConvertError {
@@ -86,6 +94,8 @@ impl fmt::Display for ConvertError {
                 write!(f, "Failed to gracefully shutdown the client: {:?}", s)
             }
             ConvertError::Send(ref err) => fmt::Display::fmt(err, f),
+            ConvertError::Config(ref s) => write!(f, "Invalid configuration: {}.", s),
+            ConvertError::Client(ref s) => write!(f, "Influxdb request failed: {}.", s),
         }
     }
 }
@@ -98,6 +108,8 @@ impl Error for ConvertError {
             ConvertError::Join(_) => "Failed to gracefully shutdown the influx client",
             ConvertError::Influx(ref err) => err.description(),
             ConvertError::Send(ref err) => err.description(),
+            ConvertError::Config(ref s) => s,
+            ConvertError::Client(ref s) => s,
         }
     }
 }
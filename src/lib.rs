@@ -0,0 +1,80 @@
+//    x-influx is a simple cli tool to import data into influxdb.
+//    Copyright (C) 2018  Henrik Jürges
+//
+//    This program is free software: you can redistribute it and/or modify
+//    it under the terms of the GNU General Public License as published by
+//    the Free Software Foundation, either version 3 of the License, or
+//    (at your option) any later version.
+//
+//    This program is distributed in the hope that it will be useful,
+//    but WITHOUT ANY WARRANTY; without even the implied warranty of
+//    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//    GNU General Public License for more details.
+//
+//    You should have received a copy of the GNU General Public License
+//    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//! x-influx
+//!
+//! This library feeds influxdb with input data
+//! from some source. A `Mapper` reads the source
+//! with some `Layout` and passes the points to a
+//! `Sink`, e.g. the `InfluxClient`.
+//!
+//! ```
+//! use std::cell::RefCell;
+//! use x_influx::{ConvertResult, Csv, Layout, Message, Sink};
+//!
+//! struct Points(RefCell<Vec<Message>>);
+//!
+//! impl Sink for Points {
+//!     fn send(&self, msg: Message) -> ConvertResult<()> {
+//!         self.0.borrow_mut().push(msg);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let data = "timestamp,data\n2016-01-01 00:15:00,108\n";
+//! let points = Points(RefCell::new(vec![]));
+//! Csv::new(vec![], false)
+//!     .import_reader("memory", data.as_bytes(), &Layout::default(), &points)
+//!     .unwrap();
+//! assert_eq!(points.0.borrow().len(), 1);
+//! ```
+//!
+//! License is GPL
+extern crate arrow_array;
+extern crate arrow_ipc;
+extern crate arrow_schema;
+extern crate bzip2;
+extern crate calamine;
+extern crate chrono;
+extern crate chrono_tz;
+extern crate encoding_rs;
+extern crate encoding_rs_io;
+extern crate flate2;
+extern crate glob;
+extern crate influent;
+extern crate parquet;
+extern crate regex;
+extern crate rpassword;
+extern crate rusqlite;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sha2;
+extern crate toml;
+extern crate xz2;
+extern crate zstd;
+
+#[macro_use]
+pub mod error;
+pub mod client;
+pub mod config;
+pub mod mapper;
+
+pub use client::{FieldValue, InfluxClient, Message};
+pub use config::{Config, Profile};
+pub use error::{ConvertError, ConvertResult};
+pub use mapper::{Arrow, Csv, FieldType, Format, Importer, Interactive, Json, Layout, LineProtocol,
+                 Log, Mapper, Mscons, Options, Prometheus, Sheet, Sink, Sql};
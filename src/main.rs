@@ -19,22 +19,24 @@
 //! from some source.
 //!
 //! License is GPL
-//! See `USAGE` for arguments.
+//! See `USAGE` for the commands and
+//! `cmd` for their arguments.
 extern crate chrono;
+extern crate ctrlc;
 extern crate docopt;
-extern crate influent;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+#[macro_use]
+extern crate x_influx;
 
-use docopt::Docopt;
+use std::env;
+use std::process;
 
-#[macro_use]
-mod error;
-mod client;
-mod mapper;
+use x_influx::{client, config, error, mapper};
 
-pub use client::InfluxClient;
-pub use mapper::{Csv, Interactive, Layout, Mapper};
+mod cmd;
 
 const VERSION: &'static str = "
 Version 0.5 of x-influx.
@@ -46,110 +48,62 @@ This is free software, and you are welcome to redistribute it
 under certain conditions; see LICENSE file for details.
 ";
 
-/// Program Flags and Options
+/// Program commands
 const USAGE: &'static str = "
-Usage: 
-  x-influx i [options]
-  x-influx b [options] <file>... 
-  x-influx [options] <file>... 
-  x-influx (-h | --help) | --version
+Usage:
+  x-influx <command> [<args>...]
+  x-influx [options] <file>...
+  x-influx (-h | --help) | (-V | --version)
 
 Options:
   -h, --help           Print this help message.
-  -v, --verbose        Enable verbose logging.
   -V, --version        Shows version and license information.
-  -u, --user USER      Username for influxdb [default: test].
-  -p, --password PASS  Password for influxdb [default: ].
-  -d, --database DB    Influx database [default: test].
-  -s, --server SRV     The influxdb server for import 
-                       [default: http://localhost:8086].
-  
-  -S, --series VAL     Name of the measurement series [default: series]
-  -m, --measure VAL    Name of the measurement value [default: data].
-  -t, --tags VAL       Comma separated list of tags associated to a value.
-  -T, --time VAL       Name of the timestamp column [default: timestamp].
-  -f, --format FMT     The timestamp format [default: %F %H:%M:%S]
-                       See https://docs.rs/chrono/0.4.0/chrono/format/strftime/index.html
 
-  -D, --delimiter DEL  Use another csv delimiter [default: ,].
-  --skip-rows NUM      Remove first NUM lines from file [default: 0].
+Commands:
+  import       Import csv files into influxdb.
+  interactive  Read points from stdin.
+  inspect      Show how the columns of a file are mapped.
+  validate     Check files against a layout without writing.
+  watch        Import files dropped into a directory.
+  export       Write the points of a series as csv.
+  replay       Send the messages of a journal again.
+  ping         Check the connection to influxdb.
+  infer        Propose a layout for a sample file.
+  layout       Write the layout options to a file.
+
+See 'x-influx <command> --help' for the options of a command.
+'x-influx [options] <file>...' is short for 'x-influx import',
+also if a file is named like a command.
 ";
 
-#[derive(Debug, Deserialize)]
-struct Args {
-    flag_verbose: bool,
-    flag_version: bool,
-    cmd_i: bool, // interactive mode
-    cmd_b: bool, // batch mode
-    flag_user: String,
-    flag_password: String,
-    flag_database: String,
-    flag_server: String,
-    flag_series: String,
-    flag_measure: String,
-    flag_tags: String,
-    flag_time: String,
-    flag_format: String,
-    flag_delimiter: char,
-    flag_skip_rows: usize,
-    arg_file: Vec<String>,
-}
-
 fn main() {
-    let _args: Args = Docopt::new(USAGE)
-        .and_then(|d| d.deserialize())
-        .unwrap_or_else(|e| e.exit());
-
-    if _args.flag_version {
-        println!("{}", VERSION);
-    }
-
-    if _args.flag_verbose {
-        set_debug!();
-    }
-
-    debug!(format!("{:?}", _args));
-    let client = match InfluxClient::new(
-        _args.flag_server,
-        _args.flag_user,
-        _args.flag_password,
-        _args.flag_database,
-        _args.flag_series,
-    ) {
-        Ok(c) => {
-            info!("Background influx client up and running.");
-            c
+    let mut argv: Vec<String> = env::args().collect();
+    match argv.get(1).map(|a| a.as_str()) {
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE.trim());
+            return;
         }
-        Err(e) => {
-            println!("Failed to start client. {}", e);
+        Some("-V") | Some("--version") => {
+            println!("{}", VERSION);
             return;
         }
-    };
-
-    let layout = Layout {
-        measure: _args.flag_measure,
-        tags: _args.flag_tags.split(',').map(|e| e.to_owned()).collect(),
-        time: _args.flag_time,
-        tformat: _args.flag_format,
-    };
-    debug!(format!("{:?}", layout));
+        Some(_) => (),
+        None => {
+            println!("{}", USAGE.trim());
+            process::exit(1);
+        }
+    }
 
-    let mapper: Box<Mapper> = match _args.cmd_i {
-        true => Box::new(Interactive {}),
-        false => Box::new(Csv::new(
-            _args.arg_file,
-            _args.cmd_b,
-            _args.flag_delimiter,
-            _args.flag_skip_rows,
-        )),
-    };
+    let command = cmd::select(&mut argv);
 
-    if let Err(e) = mapper.import(&layout, &client) {
-        error!(format!("Import failed {}", e));
+    let args = cmd::parse(command, argv);
+    if args.flag_verbose {
+        set_debug!();
     }
+    debug!(format!("{:?}", args.redacted()));
 
-    match client.join() {
-        Ok(_) => info!("Graceful shutdown."),
-        Err(e) => error!(format!("{}", e)),
+    if let Err(e) = (command.execute)(args) {
+        error!(format!("{}", e));
+        process::exit(1);
     }
 }
@@ -0,0 +1,429 @@
+//! Import parquet and arrow ipc files batch by batch.
+//!
+//! The columns are mapped by their names like the columns of a
+//! csv file. Only the columns named by the layout are read, and
+//! parquet files are decoded one row group after another, so large
+//! files are never loaded at once. Typed columns become typed fields
+//! and timestamp or date columns are used without parsing, other time
+//! columns are parsed with the time format of the layout.
+//!
+//! ```toml
+//! format = "parquet"
+//! measure = "temperature"
+//! time = "ts"
+//! tags = ["sensor"]
+//! ```
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufReader;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::*;
+use arrow_array::{Array, RecordBatchReader};
+use arrow_ipc::reader::{FileReader, StreamReader};
+use arrow_schema::{ArrowError, DataType, TimeUnit};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use client::{FieldValue, Message};
+use super::{Driver, Format, Layout, Options, Sink};
+use error::{ConvertError, ConvertResult};
+
+/// Rows of the batches read at once.
+const BATCH: usize = 8192;
+
+/// An import error of some file.
+fn failed<E: ::std::fmt::Display>(file: &str, e: E) -> ConvertError {
+    ConvertError::Import(format!("{}: {}", file, e))
+}
+
+/// The typed value of some cell, none if it is null.
+fn cell(array: &dyn Array, i: usize) -> ConvertResult<Option<FieldValue>> {
+    if array.is_null(i) {
+        return Ok(None);
+    }
+    let value = match *array.data_type() {
+        DataType::Boolean => FieldValue::Boolean(array.as_boolean().value(i)),
+        DataType::Int8 => FieldValue::Integer(array.as_primitive::<Int8Type>().value(i).into()),
+        DataType::Int16 => FieldValue::Integer(array.as_primitive::<Int16Type>().value(i).into()),
+        DataType::Int32 => FieldValue::Integer(array.as_primitive::<Int32Type>().value(i).into()),
+        DataType::Int64 => FieldValue::Integer(array.as_primitive::<Int64Type>().value(i)),
+        DataType::UInt8 => FieldValue::Integer(array.as_primitive::<UInt8Type>().value(i).into()),
+        DataType::UInt16 => FieldValue::Integer(array.as_primitive::<UInt16Type>().value(i).into()),
+        DataType::UInt32 => FieldValue::Integer(array.as_primitive::<UInt32Type>().value(i).into()),
+        DataType::UInt64 => {
+            let v = array.as_primitive::<UInt64Type>().value(i);
+            FieldValue::Integer(
+                i64::try_from(v)
+                    .map_err(|_| ConvertError::Import(format!("Integer {} is out of range", v)))?,
+            )
+        }
+        DataType::Float32 => FieldValue::Float(array.as_primitive::<Float32Type>().value(i).into()),
+        DataType::Float64 => FieldValue::Float(array.as_primitive::<Float64Type>().value(i)),
+        DataType::Decimal128(_, scale) => {
+            let v = array.as_primitive::<Decimal128Type>().value(i);
+            FieldValue::Float(v as f64 / 10f64.powi(scale.into()))
+        }
+        DataType::Utf8 => FieldValue::String(array.as_string::<i32>().value(i).into()),
+        DataType::LargeUtf8 => FieldValue::String(array.as_string::<i64>().value(i).into()),
+        DataType::Utf8View => FieldValue::String(array.as_string_view().value(i).into()),
+        // categorical columns look up their key
+        DataType::Dictionary(_, _) => {
+            let dictionary = array.as_any_dictionary();
+            return match cell(dictionary.keys(), i)? {
+                Some(FieldValue::Integer(k)) => cell(dictionary.values().as_ref(), k as usize),
+                _ => Ok(None),
+            };
+        }
+        DataType::Timestamp(_, _) | DataType::Date32 | DataType::Date64 => {
+            let t = time(&Layout::default(), array, i)?;
+            FieldValue::String(t.map(|t| t.to_rfc3339()).unwrap_or_default())
+        }
+        ref t => {
+            return Err(ConvertError::Import(format!(
+                "Columns of type {} are not supported",
+                t
+            )))
+        }
+    };
+    Ok(Some(value))
+}
+
+/// The time of some timestamp or date cell, dates are
+/// read in the timezone of the layout.
+fn time(layout: &Layout, array: &dyn Array, i: usize) -> ConvertResult<Option<DateTime<Utc>>> {
+    let days = |d: i64| NaiveDate::from_num_days_from_ce_opt(i32::try_from(d + 719_163).ok()?);
+    let t = match *array.data_type() {
+        DataType::Timestamp(TimeUnit::Second, _) => {
+            DateTime::from_timestamp(array.as_primitive::<TimestampSecondType>().value(i), 0)
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, _) => DateTime::from_timestamp_millis(
+            array.as_primitive::<TimestampMillisecondType>().value(i),
+        ),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => DateTime::from_timestamp_micros(
+            array.as_primitive::<TimestampMicrosecondType>().value(i),
+        ),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => Some(DateTime::from_timestamp_nanos(
+            array.as_primitive::<TimestampNanosecondType>().value(i),
+        )),
+        DataType::Date32 | DataType::Date64 => {
+            let d = match *array.data_type() {
+                DataType::Date32 => days(array.as_primitive::<Date32Type>().value(i).into()),
+                _ => days(
+                    array
+                        .as_primitive::<Date64Type>()
+                        .value(i)
+                        .div_euclid(86_400_000),
+                ),
+            };
+            let naive = d.ok_or_else(|| ConvertError::Import("Date is out of range".into()))?;
+            let naive = naive.and_time(Default::default());
+            match layout.timezone()? {
+                None => Some(Utc.from_utc_datetime(&naive)),
+                Some(tz) => tz
+                    .from_local_datetime(&naive)
+                    .earliest()
+                    .map(|t| t.with_timezone(&Utc)),
+            }
+        }
+        // other columns are parsed with the time format
+        _ => {
+            return match cell(array, i)? {
+                None => Ok(None),
+                Some(FieldValue::String(s)) => layout.parse_time(&s).map(Some),
+                Some(v) => layout.parse_time(&v.to_string()).map(Some),
+            }
+        }
+    };
+    t.ok_or_else(|| ConvertError::Import("Timestamp is out of range".into()))
+        .map(Some)
+}
+
+/// Positions of the measure, time and named tag columns.
+struct Columns {
+    measure: usize,
+    time: usize,
+    tags: Vec<(String, usize)>,
+}
+
+/// A convenient struct to import parquet and arrow ipc files.
+///
+/// Each row of the file becomes a point.
+#[derive(Debug, Default)]
+pub struct Arrow {
+    options: Options,
+}
+
+impl Arrow {
+    pub fn new(options: Options) -> Arrow {
+        Arrow { options }
+    }
+
+    /// Open some parquet file with the columns of the layout,
+    /// the rows up to `skip` are left out.
+    fn parquet(
+        &self,
+        file: &str,
+        layout: &Layout,
+        skip: usize,
+    ) -> ConvertResult<Box<dyn RecordBatchReader>> {
+        let f = File::open(file).map_err(|e| failed(file, e))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| failed(file, e))?;
+        let names: Vec<&String> = Some(&layout.measure)
+            .into_iter()
+            .chain(Some(&layout.time))
+            .chain(&layout.tags)
+            .collect();
+        let roots: Vec<usize> = builder
+            .schema()
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|&(_, f)| names.contains(&f.name()))
+            .map(|(i, _)| i)
+            .collect();
+        let mask = ProjectionMask::roots(builder.parquet_schema(), roots);
+        let mut builder = builder
+            .with_projection(mask)
+            .with_batch_size(BATCH)
+            .with_offset(skip);
+        if let Some(l) = self.options.limit {
+            builder = builder.with_limit(l);
+        }
+        let reader = builder.build().map_err(|e| failed(file, e))?;
+        Ok(Box::new(reader))
+    }
+
+    /// Open some arrow ipc file or stream.
+    fn ipc(&self, file: &str) -> ConvertResult<Box<dyn RecordBatchReader>> {
+        let open = || File::open(file).map_err(|e| failed(file, e));
+        match FileReader::try_new_buffered(open()?, None) {
+            Ok(r) => Ok(Box::new(r)),
+            Err(_) => StreamReader::try_new(BufReader::new(open()?), None)
+                .map(|r| Box::new(r) as Box<dyn RecordBatchReader>)
+                .map_err(|e| failed(file, e)),
+        }
+    }
+
+    /// Convert a single row of some batch.
+    fn convert(
+        &self,
+        layout: &Layout,
+        columns: &Columns,
+        arrays: &[&dyn Array],
+        i: usize,
+    ) -> ConvertResult<Message> {
+        let null = |name: &str| ConvertError::Import(format!("Column {} is null", name));
+        let value = match (
+            layout.types.contains_key(&layout.measure),
+            cell(arrays[columns.measure], i)?,
+        ) {
+            (_, None) => return Err(null(&layout.measure)),
+            (true, Some(FieldValue::String(s))) => layout.parse_value(&layout.measure, &s)?,
+            (true, Some(v)) => layout.parse_value(&layout.measure, &v.to_string())?,
+            (false, Some(v)) => v,
+        };
+        let timestamp = time(layout, arrays[columns.time], i)?.ok_or_else(|| null(&layout.time))?;
+
+        // null tags are left out
+        let mut tags = vec![];
+        for &(ref name, c) in &columns.tags {
+            if let Some(t) = cell(arrays[c], i)? {
+                tags.push((name.clone(), t.to_string()));
+            }
+        }
+
+        Ok(Message::new(
+            layout.series.clone(),
+            timestamp,
+            (layout.measure.clone(), value),
+            tags,
+        ))
+    }
+
+    /// Read a single file and pass its rows to the sink.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let resume = self.options.resume(file);
+        let (reader, skipped) = match layout.format(file) {
+            Format::Parquet => (self.parquet(file, layout, resume)?, resume),
+            _ => (self.ipc(file)?, 0),
+        };
+
+        let schema = reader.schema();
+        let pos = |name: &str| {
+            schema
+                .index_of(name)
+                .map_err(|_| ConvertError::NotFound(name.into()))
+        };
+        let columns = Columns {
+            measure: pos(&layout.measure)?,
+            time: pos(&layout.time)?,
+            tags: layout
+                .tags
+                .iter()
+                .filter_map(|t| pos(t).ok().map(|p| (t.clone(), p)))
+                .collect(),
+        };
+
+        let mut driver = Driver::new(&self.options, file, sink);
+        let mut row = skipped;
+        'batches: for batch in reader {
+            let batch = batch.map_err(|e: ArrowError| failed(file, e))?;
+            let arrays: Vec<&dyn Array> = batch.columns().iter().map(|a| a.as_ref()).collect();
+            for i in 0..batch.num_rows() {
+                row += 1;
+                if !driver.row(row, || Some(self.convert(layout, &columns, &arrays, i))) {
+                    break 'batches;
+                }
+            }
+        }
+        driver.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrow_array::{
+        DictionaryArray, Float64Array, Int32Array, RecordBatch, StringArray,
+        TimestampMillisecondArray, TimestampNanosecondArray,
+    };
+    use arrow_ipc::writer::StreamWriter;
+    use parquet::arrow::ArrowWriter;
+    use mapper::test::{temp, Collect};
+    use parquet::file::properties::WriterProperties;
+    use std::sync::Arc;
+
+    fn batch() -> RecordBatch {
+        let sensors: DictionaryArray<Int32Type> = vec!["a", "b", "a"].into_iter().collect();
+        RecordBatch::try_from_iter(vec![
+            (
+                "ts",
+                Arc::new(
+                    TimestampMillisecondArray::from(vec![0, 900_000, 1_800_250])
+                        .with_timezone("UTC"),
+                ) as Arc<dyn Array>,
+            ),
+            (
+                "temperature",
+                Arc::new(Float64Array::from(vec![Some(20.5), None, Some(21.0)])),
+            ),
+            ("count", Arc::new(Int32Array::from(vec![1, 2, 3]))),
+            ("sensor", Arc::new(sensors)),
+            ("unused", Arc::new(StringArray::from(vec!["x", "y", "z"]))),
+        ])
+        .unwrap()
+    }
+
+    fn layout() -> Layout {
+        Layout {
+            measure: "temperature".into(),
+            time: "ts".into(),
+            tags: vec!["sensor".into(), "missing".into()],
+            ..Layout::default()
+        }
+    }
+
+    #[test]
+    fn test_parquet() {
+        let path = temp("sensors.parquet");
+        let file = path.to_str().unwrap();
+        // small row groups are read one after another
+        let props = WriterProperties::builder()
+            .set_max_row_group_row_count(Some(2))
+            .build();
+        let mut writer =
+            ArrowWriter::try_new(File::create(&path).unwrap(), batch().schema(), Some(props))
+                .unwrap();
+        writer.write(&batch()).unwrap();
+        writer.close().unwrap();
+
+        let sink = Collect::default();
+        Arrow::default()
+            .import_file(file, &layout(), &sink)
+            .unwrap();
+        {
+            let sent = sink.sent.borrow();
+            assert_eq!(sent.len(), 2);
+            assert_eq!(sent[0].value().1, FieldValue::Float(20.5));
+            assert_eq!(sent[1].time().to_rfc3339(), "1970-01-01T00:30:00.250+00:00");
+            assert_eq!(sent[1].tags(), &[("sensor".to_string(), "a".to_string())]);
+            assert_eq!(*sink.rejected.borrow(), vec![2]);
+        }
+
+        let counts = Layout {
+            measure: "count".into(),
+            ..layout()
+        };
+        let sink = Collect::default();
+        let options = Options {
+            resume: vec![(file.to_string(), 2)].into_iter().collect(),
+            ..Options::default()
+        };
+        Arrow::new(options)
+            .import_file(file, &counts, &sink)
+            .unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(sink.sent.borrow()[0].value().1, FieldValue::Integer(3));
+
+        let missing = Layout {
+            measure: "unknown".into(),
+            ..layout()
+        };
+        assert!(Arrow::default().import_file(file, &missing, &sink).is_err());
+    }
+
+    #[test]
+    fn test_ipc() {
+        let path = temp("sensors.arrows");
+        let file = path.to_str().unwrap();
+        let mut writer =
+            StreamWriter::try_new(File::create(&path).unwrap(), &batch().schema()).unwrap();
+        writer.write(&batch()).unwrap();
+        writer.finish().unwrap();
+
+        let mut layout = layout();
+        layout
+            .types
+            .insert("temperature".into(), ::mapper::FieldType::String);
+        let sink = Collect::default();
+        let options = Options {
+            limit: Some(1),
+            ..Options::default()
+        };
+        Arrow::new(options)
+            .import_file(file, &layout, &sink)
+            .unwrap();
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].value().1, FieldValue::String("20.5".into()));
+        assert_eq!(sent[0].time().timestamp(), 0);
+    }
+
+    #[test]
+    fn test_nanoseconds() {
+        let path = temp("nanos.arrows");
+        let file = path.to_str().unwrap();
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "ts",
+                Arc::new(TimestampNanosecondArray::from(vec![1_465_839_830_100_400_200]))
+                    as Arc<dyn Array>,
+            ),
+            ("temperature", Arc::new(Float64Array::from(vec![20.5]))),
+        ])
+        .unwrap();
+        let mut writer =
+            StreamWriter::try_new(File::create(&path).unwrap(), &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+
+        let sink = Collect::default();
+        Arrow::default()
+            .import_file(file, &layout(), &sink)
+            .unwrap();
+        let sent = sink.sent.borrow();
+        assert_eq!(sent[0].time().timestamp_nanos_opt(), Some(1_465_839_830_100_400_200));
+    }
+}
@@ -2,16 +2,28 @@
 //! front rows and ignore columns.
 use super::*;
 
-use std::fs::File;
 use std::iter::Iterator;
-use std::io::{BufRead, BufReader, Lines, Read};
+use std::io::{BufRead, Lines};
+use chrono::{DateTime, Utc};
 use client::Message;
-use chrono::{TimeZone, Utc};
+use super::{preamble, source};
+use super::source::Source;
+use super::follow::{Event, Tail};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// How often followed files are checked for new rows.
+const POLL: Duration = Duration::from_millis(500);
+
+/// Positions of the measure, time and tag columns,
+/// the tags with their names.
+pub type Columns = (usize, usize, Vec<(String, usize)>);
 
 /// A convenient struct to model csv data.
 ///
 /// To react to inappropriate csv files
-/// we can change the delimiter, skip initial
+/// the layout can change the delimiter, skip initial
 /// rows and only columns named by the layout
 /// are inserted.
 ///
@@ -22,142 +34,353 @@ use chrono::{TimeZone, Utc};
 pub struct Csv {
     files: Vec<String>,
     batch: bool,
-    delimiter: char,
-    first_row: usize,
+    options: Options,
+}
+
+/// A file which is followed for new rows. The header positions
+/// are remembered if the file is rotated or truncated.
+struct Followed<'a> {
+    file: &'a str,
+    base: &'a Layout,
+    tail: Tail,
+    row: usize,
+    preamble: Vec<String>,
+    layout: Layout,
+    statics: Vec<(String, String)>,
+    header: Option<Columns>,
+    legends: Legends,
 }
 
 impl Csv {
-    pub fn new(files: Vec<String>, batch: bool, del: char, row: usize) -> Csv {
+    pub fn new(files: Vec<String>, batch: bool) -> Csv {
         Csv {
             files: files,
             batch: batch,
-            delimiter: del,
-            first_row: row,
+            options: Options::default(),
         }
     }
 
-    /// Return the position of some field in the header row or not found error.
-    fn find_pos(&self, name: &str, data: &[String]) -> ConvertResult<usize> {
-        data.iter()
-            .position(|&ref e| e == name)
-            .ok_or(ConvertError::NotFound(name.into()))
+    /// Only read some rows and skip the ones of a former run.
+    pub fn with_options(mut self, options: Options) -> Csv {
+        self.options = options;
+        self
     }
 
-    /// Return an array of column positions corresponding to the names
-    /// or an empty list of nothing is found.
-    fn find_positions(&self, names: &[String], data: &[String]) -> Vec<usize> {
-        names
-            .iter()
-            .filter_map(|t| self.find_pos(t, data).ok())
-            .collect()
+    /// Split a line with the delimiter or the columns of the layout.
+    fn split(&self, layout: &Layout, line: &str) -> Vec<String> {
+        layout.split(line)
     }
 
-    /// Split a line with a given delimiter
-    fn split(&self, line: &str) -> Vec<String> {
-        line.split(self.delimiter).map(|e| e.into()).collect()
-    }
-
-    /// skip initial rows and return header line if found.
-    fn skip<R: Read>(&self, lines: Lines<BufReader<R>>) -> ConvertResult<String> {
-        match lines.skip(self.first_row).next() {
+    /// Skip initial rows and return them with the header line if found.
+    fn skip<R: BufRead>(
+        &self,
+        layout: &Layout,
+        lines: &mut Lines<R>,
+    ) -> ConvertResult<(Vec<String>, String)> {
+        let err = |e| ConvertError::Import(format!("{:?}", e));
+        let mut preamble = vec![];
+        for _ in 0..layout.skip_rows {
+            match lines.next() {
+                None => break,
+                Some(l) => preamble.push(l.map_err(err)?),
+            }
+        }
+        match lines.next() {
             None => Err(ConvertError::Import("Header not found".into())),
-            Some(l) => l.map_err(|e| ConvertError::Import(format!("{:?}", e))),
+            Some(l) => l.map(|h| (preamble, h)).map_err(err),
         }
     }
 
+    /// Return the header row of some csv text.
+    pub fn header<R: BufRead>(&self, reader: R, layout: &Layout) -> ConvertResult<Vec<String>> {
+        self.skip(layout, &mut reader.lines())
+            .map(|(_, h)| layout.split_header(&h))
+    }
+
     /// Search for the column positions of measure, time and tags columns.
-    fn read_header<R: Read>(
+    /// The lines are consumed up to the header row.
+    fn read_header<R: BufRead>(
         &self,
         layout: &Layout,
-        lines: Lines<BufReader<R>>,
-    ) -> ConvertResult<(usize, usize, Vec<usize>)> {
-        let header = try!(self.skip(lines).and_then(|e| Ok(self.split(&e))));
-        let measure = try!(self.find_pos(&layout.measure, header.as_slice()));
-        let time = try!(self.find_pos(&layout.time, header.as_slice()));
-        let tags = self.find_positions(&layout.tags, header.as_slice());
-        Ok((measure, time, tags))
+        lines: &mut Lines<R>,
+    ) -> ConvertResult<(Vec<String>, Columns)> {
+        let (preamble, header) = self.skip(layout, lines)?;
+        Ok((preamble, self.positions(layout, &header)?))
     }
 
-    fn open(&self, f: &str) -> ConvertResult<BufReader<File>> {
-        File::open(f)
-            .map_err(|e| ConvertError::Import(format!("Failed to import file {} : {}", f, e)))
-            .and_then(|e| Ok(BufReader::new(e)))
+    /// Return the column positions within some header line.
+    fn positions(&self, layout: &Layout, header: &str) -> ConvertResult<Columns> {
+        find_columns(layout, &layout.split_header(header))
     }
-}
 
-impl Mapper for Csv {
-    // too much trys where skipping this row is a better solution
-    fn import(&self, layout: &Layout, client: &InfluxClient) -> ConvertResult<()> {
-        for file in &self.files {
-            debug!(format!("Opening {:?}", file));
-            let reader = try!(self.open(file));
-
-            let (measure, time, tags) = match self.read_header(layout, reader.lines()) {
-                Err(e) => {
-                    error!(format!("Failed to parse header: {}", e));
-                    continue;
-                }
-                Ok((m, t, ta)) => (m, t, ta),
-            };
-
-            debug!(format!(
-                "Found header: Measure: {}, time: {}, tags: {:?}",
-                measure, time, tags
-            ));
-
-            let reader = try!(self.open(file));
-            let mut lines = reader.lines().skip(self.first_row + 1);
-            for line in lines {
-                let data = try!(line.and_then(|l| Ok(self.split(&l))));
-
-                let value = (layout.measure.clone(), data[measure].clone());
-                let timestamp = match Utc.datetime_from_str(&data[time], &layout.tformat) {
-                    Ok(t) => t,
-                    Err(e) => {
-                        error!(format!("Failed to parse date {}", e));
-                        continue;
-                    }
-                };
-
-                let mut t = vec![];
-                for (i, n) in tags.iter().enumerate() {
-                    t.push((layout.tags[i].clone(), data[*n].clone()));
+    /// Open some file decoded with the encoding of the layout.
+    pub fn open(&self, f: &str, layout: &Layout) -> ConvertResult<Source> {
+        source::open(f, layout.encoding.as_deref())
+    }
+
+    /// Convert a single row with the column positions found by `read_header`.
+    /// The static tags taken from the preamble are added to every point.
+    fn convert(
+        &self,
+        layout: &Layout,
+        header: &Columns,
+        statics: &[(String, String)],
+        line: &str,
+    ) -> ConvertResult<Message> {
+        convert_cells(layout, header, statics, &self.split(layout, line))
+    }
+
+    /// Read a single file and pass its rows to the sink.
+    /// Rows which can't be converted are rejected and skipped.
+    /// The file `-` is read from stdin.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        self.import_reader(file, self.open(file, layout)?, layout, sink)
+    }
+
+    /// Read csv text from some reader and pass its rows to the sink.
+    /// The header and the rows are read in a single pass, `source`
+    /// names the reader in rejected rows.
+    pub fn import_reader<R: BufRead>(
+        &self,
+        source: &str,
+        reader: R,
+        layout: &Layout,
+        sink: &dyn Sink,
+    ) -> ConvertResult<()> {
+        let mut lines = reader.lines();
+        let header = self.read_header(layout, &mut lines).and_then(|(preamble, h)| {
+            preamble::apply(layout, &preamble).map(|(l, statics)| (l, statics, h))
+        });
+        let (layout, statics, header) = match header {
+            Err(e) => {
+                sink.reject(source, layout.skip_rows + 1, e);
+                return Ok(());
+            }
+            Ok(h) => h,
+        };
+        let layout = &layout;
+
+        debug!(format!(
+            "Found header: Measure: {}, time: {}, tags: {:?}",
+            header.0, header.1, header.2
+        ));
+
+        let mut driver = Driver::new(&self.options, source, sink);
+        driver.skip(layout.skip_rows + 1);
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let row = layout.skip_rows + i + 2;
+            if !driver.row(row, || Some(self.convert(layout, &header, &statics, &line))) {
+                break;
+            }
+        }
+        driver.finish();
+        Ok(())
+    }
+
+    /// Handle a single line of some followed file.
+    fn follow_line(&self, f: &mut Followed, line: &str, sink: &dyn Sink) {
+        f.row += 1;
+        if f.row <= f.base.skip_rows {
+            f.preamble.push(line.to_owned());
+            return;
+        }
+
+        if f.row == f.base.skip_rows + 1 {
+            let header = self.positions(f.base, line).and_then(|h| {
+                preamble::apply(f.base, &f.preamble).map(|(l, statics)| (h, l, statics))
+            });
+            match header {
+                Ok((h, layout, statics)) => {
+                    debug!(format!("Found header in {}: {:?}", f.file, h));
+                    f.header = Some(h);
+                    f.layout = layout;
+                    f.statics = statics;
+                    return;
                 }
+                // a new file without header uses the remembered one
+                Err(e) => if f.header.is_none() {
+                    sink.reject(f.file, f.row, e);
+                    return;
+                },
+            }
+        }
 
-                let msg = Message::new(timestamp, value, t);
-                debug!(format!("Sending: {:?}", msg));
-                if let Err(e) = client.send(msg) {
+        if let Some(ref header) = f.header {
+            match self.convert(&f.layout, header, &f.statics, line) {
+                Ok(msg) => if let Err(e) = f.legends.sink(sink).send(msg) {
                     error!(format!("Failed to import file: {}", e));
+                },
+                Err(e) => sink.reject(f.file, f.row, e),
+            }
+        }
+    }
+
+    /// Pass the new rows of some followed file to the sink.
+    fn follow_file(&self, f: &mut Followed, sink: &dyn Sink) {
+        let events = match f.tail.poll() {
+            Ok(e) => e,
+            Err(e) => {
+                error!(format!("Failed to read {}: {}", f.file, e));
+                return;
+            }
+        };
+        for event in events {
+            match event {
+                Event::Line(l) => self.follow_line(f, &l, sink),
+                Event::Reset => {
+                    f.row = 0;
+                    f.preamble.clear();
                 }
             }
         }
+    }
+
+    /// Read the given files with their layouts and their new rows
+    /// until stopped, rotated and truncated files are read from
+    /// the beginning.
+    pub fn follow(
+        &self,
+        files: &[(&str, &Layout)],
+        sink: &dyn Sink,
+        stop: &AtomicBool,
+    ) -> ConvertResult<()> {
+        let mut followed = vec![];
+        for &(file, layout) in files {
+            followed.push(Followed {
+                file,
+                base: layout,
+                tail: Tail::new(file, layout.encoding.as_deref())?,
+                row: 0,
+                preamble: vec![],
+                layout: layout.clone(),
+                statics: vec![],
+                header: None,
+                legends: Legends::new(layout)?,
+            });
+        }
+
+        info!(format!("Following {} files until interrupted.", followed.len()));
+        while !stop.load(Ordering::SeqCst) {
+            for f in &mut followed {
+                self.follow_file(f, sink);
+            }
+            thread::sleep(POLL);
+        }
         Ok(())
     }
 }
 
+impl Mapper for Csv {
+    fn import(&self, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        for (i, file) in self.files.iter().enumerate() {
+            info!(format!("[{}/{}] {}", i + 1, self.files.len(), file));
+            debug!(format!("Opening {:?} with layout {:?}", file, layout));
+            self.import_file(file, layout, sink)?;
+        }
+        Ok(())
+    }
+}
+
+/// Return the position of some field in the header row or not found error.
+fn find_pos(name: &str, data: &[String]) -> ConvertResult<usize> {
+    data.iter()
+        .position(|&ref e| e == name)
+        .ok_or(ConvertError::NotFound(name.into()))
+}
+
+/// Return the names found in the data with their column positions
+/// or an empty list of nothing is found.
+fn find_positions(names: &[String], data: &[String]) -> Vec<(String, usize)> {
+    names
+        .iter()
+        .filter_map(|t| find_pos(t, data).ok().map(|p| (t.clone(), p)))
+        .collect()
+}
+
+/// Return the column positions within the cells of some header row.
+pub fn find_columns(layout: &Layout, header: &[String]) -> ConvertResult<Columns> {
+    let measure = find_pos(&layout.measure, header)?;
+    let time = find_pos(&layout.time, header)?;
+    let tags = find_positions(&layout.tags, header);
+    Ok((measure, time, tags))
+}
+
+/// Return some cell of a row or an error if the row is too short.
+fn cell(data: &[String], i: usize) -> ConvertResult<&String> {
+    data.get(i)
+        .ok_or_else(|| ConvertError::Import(format!("Column {} is missing", i + 1)))
+}
+
+/// Convert the cells of a single row with the column positions
+/// found by `find_columns`. The static tags taken from the
+/// preamble are added to every point.
+pub fn convert_cells(
+    layout: &Layout,
+    header: &Columns,
+    statics: &[(String, String)],
+    data: &[String],
+) -> ConvertResult<Message> {
+    let timestamp = layout.parse_time(cell(data, header.1)?)?;
+    convert_timed(layout, header, statics, data, timestamp)
+}
+
+/// Convert the cells of a single row like `convert_cells`
+/// with the time already taken from the time column.
+pub fn convert_timed(
+    layout: &Layout,
+    header: &Columns,
+    statics: &[(String, String)],
+    data: &[String],
+    timestamp: DateTime<Utc>,
+) -> ConvertResult<Message> {
+    let (measure, _, ref tags) = *header;
+    let value = layout.parse_value(&layout.measure, cell(data, measure)?)?;
+
+    let mut t = statics.to_vec();
+    for &(ref name, n) in tags {
+        t.push((name.clone(), cell(data, n)?.clone()));
+    }
+
+    Ok(Message::new(
+        layout.series.clone(),
+        timestamp,
+        (layout.measure.clone(), value),
+        t,
+    ))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use client::test;
+    use client::{test, FieldValue};
+    use mapper::test::{temp, Collect};
+    use std::io::BufReader;
 
     #[test]
     fn test_get_header_positions() {
-        let layout = Layout::default();
-        let csv = Csv::new(vec![], false, ',', 2);
-        let data = BufReader::new("dsf\nnsdfsdf\ntimestamp,data".as_bytes()).lines();
+        let layout = Layout {
+            skip_rows: 2,
+            ..Layout::default()
+        };
+        let csv = Csv::new(vec![], false);
+        let mut data = BufReader::new("dsf\nnsdfsdf\ntimestamp,data".as_bytes()).lines();
 
-        let header = csv.read_header(&layout, data);
+        let header = csv.read_header(&layout, &mut data);
         assert!(header.is_ok());
-        assert_eq!(header.unwrap(), (1, 0, vec![]));
+        assert_eq!(header.unwrap(), (vec!["dsf".into(), "nsdfsdf".into()], (1, 0, vec![])));
     }
 
     #[test]
     fn test_no_header() {
-        let layout = Layout::default();
-        let csv = Csv::new(vec![], false, ',', 2);
-        let data = BufReader::new("".as_bytes()).lines();
+        let layout = Layout {
+            skip_rows: 2,
+            ..Layout::default()
+        };
+        let csv = Csv::new(vec![], false);
+        let mut data = BufReader::new("".as_bytes()).lines();
 
-        let header = csv.read_header(&layout, data);
+        let header = csv.read_header(&layout, &mut data);
         assert!(header.is_err());
         assert_eq!(format!("{}", header.err().unwrap()), "Header not found.");
     }
@@ -168,7 +391,9 @@ mod test {
         let mut layout = Layout::default();
         layout.measure = "Profilwert kWh".into();
         layout.tformat = "%d.%m.%Y %H:%M".into();
-        let csv = Csv::new(vec!["assets/test.csv".into()], false, ';', 10);
+        layout.delimiter = ';';
+        layout.skip_rows = 10;
+        let csv = Csv::new(vec!["assets/test.csv".into()], false);
 
         let res = csv.import(&layout, &client);
         assert!(res.is_ok());
@@ -177,9 +402,184 @@ mod test {
         assert!(res.is_ok());
     }
 
+    #[test]
+    fn test_reject_rows() {
+        let layout = Layout {
+            tformat: "%d.%m.%Y %H:%M".into(),
+            types: vec![("data".to_string(), FieldType::Float)]
+                .into_iter()
+                .collect(),
+            ..Layout::default()
+        };
+        let header = (1, 0, vec![]);
+        let csv = Csv::new(vec![], false);
+        let sink = Collect::default();
+
+        assert!(csv.convert(&layout, &header, &[], "01.01.2016 00:15,108").is_ok());
+        assert!(csv.convert(&layout, &header, &[], "01.01.2016 00:15").is_err());
+        assert!(csv.convert(&layout, &header, &[], "01.01.2016,108").is_err());
+        assert!(csv.convert(&layout, &header, &[], "01.01.2016 00:15,x").is_err());
+
+        let german = Layout {
+            delimiter: ';',
+            decimal_sep: ',',
+            thousands_sep: Some('.'),
+            ..layout.clone()
+        };
+        let msg = csv.convert(&german, &header, &[], "01.01.2016 00:15;1.234,5").unwrap();
+        assert_eq!(msg.value().1, FieldValue::Float(1234.5));
+
+        // a missing tag column doesn't shift the names of the others
+        let tagged = Layout {
+            tags: vec!["meter".into(), "unit".into(), "Status".into()],
+            ..layout.clone()
+        };
+        let header = csv.positions(&tagged, "timestamp,data,meter,Status").unwrap();
+        let msg = csv.convert(&tagged, &header, &[], "01.01.2016 00:15,108,4711,220").unwrap();
+        assert_eq!(
+            msg.tags(),
+            &[
+                ("meter".to_string(), "4711".to_string()),
+                ("Status".to_string(), "220".to_string()),
+            ]
+        );
+
+        let layout = Layout {
+            measure: "Profilwert kWh".into(),
+            skip_rows: 10,
+            delimiter: ';',
+            ..layout
+        };
+        csv.import_file("assets/test.csv", &layout, &sink).unwrap();
+        assert_eq!(sink.sent.borrow().len(), 4);
+        assert!(sink.rejected.borrow().is_empty());
+
+        let csv = csv.with_options(Options {
+            limit: Some(2),
+            ..Options::default()
+        });
+        csv.import_file("assets/test.csv", &layout, &sink).unwrap();
+        assert_eq!(sink.sent.borrow().len(), 6);
+
+        let layout = Layout {
+            skip_rows: 0,
+            ..layout
+        };
+        csv.import_file("assets/test.csv", &layout, &sink).unwrap();
+        assert_eq!(*sink.rejected.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn test_preamble_tags() {
+        let layout = Layout::load("assets/test.toml").unwrap();
+        let csv = Csv::new(vec![], false);
+        let sink = Collect::default();
+        csv.import_file("assets/test.csv", &layout, &sink).unwrap();
+
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 4);
+        assert_eq!(sent[0].tags()[0], ("meter".into(), "1,53E+07".into()));
+        assert_eq!(sent[0].tags()[1], ("unit".into(), "kWh".into()));
+        assert_eq!(sent[0].tags()[2], ("Status".into(), "220".into()));
+    }
+
+    #[test]
+    fn test_compressed() {
+        use std::fs::{self, File};
+
+        let path = temp("test.csv.zst");
+        let plain = fs::read("assets/test.csv").unwrap();
+        ::zstd::stream::copy_encode(&plain[..], File::create(&path).unwrap(), 0).unwrap();
+
+        let layout = Layout::load("assets/test.toml").unwrap();
+        let sink = Collect::default();
+        let csv = Csv::new(vec![], false);
+        csv.import_file(path.to_str().unwrap(), &layout, &sink).unwrap();
+        assert_eq!(sink.sent.borrow().len(), 4);
+        assert!(sink.rejected.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_import_reader() {
+        let data = "timestamp,data\n2016-01-01 00:15:00,108\nbroken\n";
+        let csv = Csv::new(vec![], false);
+        let sink = Collect::default();
+        csv.import_reader("-", data.as_bytes(), &Layout::default(), &sink)
+            .unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(*sink.rejected.borrow(), vec![3]);
+    }
+
+    #[test]
+    fn test_follow() {
+        use std::fs;
+
+        let path = temp("follow-csv.csv");
+        fs::write(&path, "id ;\ntimestamp;data\n2016-01-01 00:15:00;1\n").unwrap();
+        let file = path.to_str().unwrap();
+        let layout = Layout {
+            delimiter: ';',
+            skip_rows: 1,
+            preamble: vec![preamble::Extract {
+                tag: Some("meter".into()),
+                row: Some(0),
+                column: Some(0),
+                ..Default::default()
+            }],
+            ..Layout::default()
+        };
+        let csv = Csv::new(vec![], false);
+        let sink = Collect::default();
+        let mut followed = Followed {
+            file,
+            base: &layout,
+            tail: Tail::new(file, None).unwrap(),
+            row: 0,
+            preamble: vec![],
+            layout: layout.clone(),
+            statics: vec![],
+            header: None,
+            legends: Legends::default(),
+        };
+
+        csv.follow_file(&mut followed, &sink);
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(sink.sent.borrow()[0].tags()[0].1, "id");
+
+        // rotated file without header keeps the header positions
+        fs::write(&path, "x\n2016-01-01 00:30:00;2\n").unwrap();
+        csv.follow_file(&mut followed, &sink);
+        assert_eq!(sink.sent.borrow().len(), 2);
+        assert!(sink.rejected.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_resume() {
+        let data = "timestamp,data\n2016-01-01 00:15:00,1\n2016-01-01 00:30:00,2\n";
+        let csv = Csv::new(vec![], false).with_options(Options {
+            resume: vec![("-".to_string(), 2)].into_iter().collect(),
+            ..Options::default()
+        });
+        let sink = Collect::default();
+        csv.import_reader("-", data.as_bytes(), &Layout::default(), &sink)
+            .unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(sink.sent.borrow()[0].value().1, FieldValue::String("2".into()));
+
+        // the limit counts the rows after the resumed ones
+        let csv = Csv::new(vec![], false).with_options(Options {
+            limit: Some(1),
+            resume: vec![("-".to_string(), 2)].into_iter().collect(),
+        });
+        let sink = Collect::default();
+        csv.import_reader("-", data.as_bytes(), &Layout::default(), &sink)
+            .unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(sink.sent.borrow()[0].value().1, FieldValue::String("2".into()));
+    }
+
     #[test]
     fn test_get_single_col() {
-        let csv = Csv::new(vec![], false, ',', 2);
         let data = vec![
             "a".into(),
             "b".into(),
@@ -190,16 +590,15 @@ mod test {
         ];
 
         let mut layout = "a";
-        assert_eq!(csv.find_pos(&layout, &data).unwrap(), 0);
+        assert_eq!(find_pos(&layout, &data).unwrap(), 0);
         layout = "f";
-        assert_eq!(csv.find_pos(&layout, &data).unwrap(), 5);
+        assert_eq!(find_pos(&layout, &data).unwrap(), 5);
         layout = "data";
-        assert!(csv.find_pos(&layout, &data).is_err());
+        assert!(find_pos(&layout, &data).is_err());
     }
 
     #[test]
     fn test_get_multiple_cols() {
-        let csv = Csv::new(vec![], false, ',', 2);
         let data = vec![
             "a".into(),
             "b".into(),
@@ -210,10 +609,10 @@ mod test {
         ];
 
         let mut layout = vec!["data".into()];
-        assert_eq!(csv.find_positions(&layout, &data), vec![]);
+        assert_eq!(find_positions(&layout, &data), vec![]);
         layout = vec!["f".into()];
-        assert_eq!(csv.find_positions(&layout, &data), vec![5]);
+        assert_eq!(find_positions(&layout, &data), vec![("f".into(), 5)]);
         layout = vec!["f".into(), "a".into()];
-        assert_eq!(csv.find_positions(&layout, &data), vec![5, 0]);
+        assert_eq!(find_positions(&layout, &data), vec![("f".into(), 5), ("a".into(), 0)]);
     }
 }
@@ -0,0 +1,165 @@
+//! Split the lines of fixed width files into columns.
+//! A column is given by its start and end offset or by its
+//! width after the former column, counted in characters.
+//! The header row and the preamble are read like the ones
+//! of csv files.
+//!
+//! ```toml
+//! format = "fixed"
+//! skip_rows = 2
+//!
+//! [[fixed.columns]]
+//! width = 16
+//!
+//! [[fixed.columns]]
+//! start = 16
+//! end = 26
+//! name = "value"
+//! ```
+use error::{ConvertError, ConvertResult};
+
+/// Columns are trimmed unless disabled.
+fn yes() -> bool {
+    true
+}
+
+/// A single column of some fixed width file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Column {
+    /// Use this name instead of the header cell.
+    pub name: Option<String>,
+    /// Offset of the first character counted from 0,
+    /// the end of the former column if not set.
+    pub start: Option<usize>,
+    /// Offset after the last character.
+    pub end: Option<usize>,
+    /// Number of characters if no end is set.
+    pub width: Option<usize>,
+    /// Remove the padding around the value.
+    #[serde(default = "yes")]
+    pub trim: bool,
+}
+
+impl Column {
+    /// A trimmed column of the given width.
+    pub fn width(width: usize) -> Column {
+        Column {
+            name: None,
+            start: None,
+            end: None,
+            width: Some(width),
+            trim: true,
+        }
+    }
+}
+
+/// The character ranges of the columns,
+/// the last column may reach to the end of the line.
+fn ranges(columns: &[Column]) -> Vec<(usize, usize)> {
+    let mut pos = 0;
+    columns
+        .iter()
+        .map(|c| {
+            let start = c.start.unwrap_or(pos);
+            let end = c.end
+                .or_else(|| c.width.map(|w| start + w))
+                .unwrap_or(usize::MAX);
+            pos = end;
+            (start, end)
+        })
+        .collect()
+}
+
+/// Return an error if the columns can't be used.
+pub fn check(columns: &[Column]) -> ConvertResult<()> {
+    if columns.is_empty() {
+        return Err(ConvertError::Config("Fixed width files need columns".into()));
+    }
+    for (i, c) in columns.iter().enumerate() {
+        if c.end.is_some() && c.width.is_some() {
+            return Err(ConvertError::Config(format!(
+                "Column {} has an end and a width",
+                i + 1
+            )));
+        }
+        if c.end.is_none() && c.width.is_none() && i + 1 < columns.len() {
+            return Err(ConvertError::Config(format!(
+                "Column {} needs an end or a width",
+                i + 1
+            )));
+        }
+    }
+    for (i, &(start, end)) in ranges(columns).iter().enumerate() {
+        if start >= end {
+            return Err(ConvertError::Config(format!("Column {} is empty", i + 1)));
+        }
+    }
+    Ok(())
+}
+
+/// Split a line into the cells of the columns.
+/// Short lines give empty cells.
+pub fn split(columns: &[Column], line: &str) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    ranges(columns)
+        .into_iter()
+        .zip(columns)
+        .map(|((start, end), c)| {
+            let cell: String = chars[start.min(chars.len())..end.min(chars.len())]
+                .iter()
+                .collect();
+            match c.trim {
+                true => cell.trim().to_owned(),
+                false => cell,
+            }
+        })
+        .collect()
+}
+
+/// Split a header line and replace the cells of named columns.
+pub fn header(columns: &[Column], line: &str) -> Vec<String> {
+    split(columns, line)
+        .into_iter()
+        .zip(columns)
+        .map(|(cell, c)| c.name.clone().unwrap_or(cell))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let rest = Column {
+            width: None,
+            ..Column::width(0)
+        };
+        let columns = vec![
+            Column::width(16),
+            Column {
+                name: Some("value".into()),
+                end: Some(26),
+                ..rest.clone()
+            },
+            Column {
+                trim: false,
+                start: Some(26),
+                ..Column::width(2)
+            },
+            rest.clone(),
+        ];
+        assert!(check(&columns).is_ok());
+
+        let line = "01.01.2016 00:15    108,5 äb rest";
+        assert_eq!(split(&columns, line), vec!["01.01.2016 00:15", "108,5", "äb", "rest"]);
+        assert_eq!(header(&columns, "timestamp       kWh"), vec!["timestamp", "value", "", ""]);
+        assert_eq!(split(&columns, "short"), vec!["short", "", "", ""]);
+
+        assert!(check(&[]).is_err());
+        assert!(check(&[rest.clone(), Column::width(1)]).is_err());
+        assert!(check(&[Column { end: Some(4), ..Column::width(2) }]).is_err());
+        assert!(check(&[Column { start: Some(5), end: Some(5), ..rest }]).is_err());
+    }
+}
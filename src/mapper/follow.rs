@@ -0,0 +1,180 @@
+//! Follow a growing file like `tail -F`.
+//!
+//! Only complete lines are returned, a partial line is kept
+//! until its newline is written. If the file is truncated or
+//! replaced by a new file during log rotation it is read from
+//! the beginning again, the rest of a rotated file is read first.
+use std::fs::{self, File, Metadata};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use encoding_rs::Encoding;
+use super::source;
+use error::{ConvertError, ConvertResult};
+
+/// Something which happened to the followed file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A complete line without its line break.
+    Line(String),
+    /// The file starts again after truncation or rotation.
+    Reset,
+}
+
+/// The read position within a followed file.
+#[derive(Debug)]
+pub struct Tail {
+    path: PathBuf,
+    reader: Option<BufReader<File>>,
+    id: u64,
+    offset: u64,
+    partial: Vec<u8>,
+    encoding: Option<&'static Encoding>,
+}
+
+/// Identify some file to notice if it is replaced.
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+/// Without inodes a replaced file is only noticed if it is shorter.
+#[cfg(not(unix))]
+fn file_id(_: &Metadata) -> u64 {
+    0
+}
+
+impl Tail {
+    /// Follow the file from its beginning with the given
+    /// or detected encoding. The file may not exist yet.
+    pub fn new(file: &str, label: Option<&str>) -> ConvertResult<Tail> {
+        if file == "-" {
+            return Err(ConvertError::Config("Can't follow stdin".into()));
+        }
+        Ok(Tail {
+            path: PathBuf::from(file),
+            reader: None,
+            id: 0,
+            offset: 0,
+            partial: vec![],
+            encoding: source::encoding(label)?,
+        })
+    }
+
+    /// Open the file from its beginning.
+    fn open(&mut self) -> ConvertResult<()> {
+        let file = File::open(&self.path)?;
+        self.id = file_id(&file.metadata()?);
+        self.reader = Some(BufReader::new(file));
+        self.offset = 0;
+        self.partial.clear();
+        Ok(())
+    }
+
+    /// Decode a line of the file and remove the line break.
+    fn decode(&mut self, bytes: &[u8]) -> String {
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        let encoding = *self.encoding.get_or_insert_with(|| source::detect(bytes));
+        encoding.decode(bytes).0.into_owned()
+    }
+
+    /// Read all complete lines up to the end of the file.
+    fn read(&mut self, events: &mut Vec<Event>) -> ConvertResult<()> {
+        loop {
+            let mut partial = ::std::mem::take(&mut self.partial);
+            let read = match self.reader {
+                Some(ref mut r) => r.read_until(b'\n', &mut partial)?,
+                None => 0,
+            };
+            if read == 0 || !partial.ends_with(b"\n") {
+                self.partial = partial;
+                return Ok(());
+            }
+            self.offset += partial.len() as u64;
+            events.push(Event::Line(self.decode(&partial)));
+        }
+    }
+
+    /// Return the events since the last poll.
+    pub fn poll(&mut self) -> ConvertResult<Vec<Event>> {
+        let mut events = vec![];
+        // the file may be missing for a moment during rotation
+        let meta = match fs::metadata(&self.path) {
+            Ok(m) => m,
+            Err(_) => return Ok(events),
+        };
+
+        if self.reader.is_none() {
+            self.open()?;
+        } else if meta.len() < self.offset + self.partial.len() as u64 {
+            info!(format!("{} was truncated", self.path.display()));
+            if let Some(ref mut r) = self.reader {
+                r.seek(SeekFrom::Start(0))?;
+            }
+            self.offset = 0;
+            self.partial.clear();
+            events.push(Event::Reset);
+        } else if file_id(&meta) != self.id {
+            info!(format!("{} was rotated", self.path.display()));
+            self.read(&mut events)?;
+            if !self.partial.is_empty() {
+                let rest = ::std::mem::take(&mut self.partial);
+                events.push(Event::Line(self.decode(&rest)));
+            }
+            self.open()?;
+            events.push(Event::Reset);
+        }
+
+        self.read(&mut events)?;
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mapper::test::temp;
+    use std::io::Write;
+
+    fn append(path: &PathBuf, text: &str) {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| f.write_all(text.as_bytes()))
+            .unwrap();
+    }
+
+    fn line(s: &str) -> Event {
+        Event::Line(s.into())
+    }
+
+    #[test]
+    fn test_follow() {
+        let path = temp("follow.csv");
+        let _ = fs::remove_file(&path);
+        let mut tail = Tail::new(path.to_str().unwrap(), None).unwrap();
+        assert!(tail.poll().unwrap().is_empty());
+
+        append(&path, "timestamp,data\r\n1,2\n3,");
+        assert_eq!(tail.poll().unwrap(), vec![line("timestamp,data"), line("1,2")]);
+        append(&path, "4\n");
+        assert_eq!(tail.poll().unwrap(), vec![line("3,4")]);
+
+        fs::write(&path, "timestamp,data\n").unwrap();
+        assert_eq!(tail.poll().unwrap(), vec![Event::Reset, line("timestamp,data")]);
+
+        append(&path, "5,6\n7,8");
+        let rotated = temp("follow.csv.1");
+        fs::rename(&path, &rotated).unwrap();
+        assert!(tail.poll().unwrap().is_empty());
+        append(&path, "timestamp,data\n");
+        assert_eq!(
+            tail.poll().unwrap(),
+            vec![line("5,6"), line("7,8"), Event::Reset, line("timestamp,data")]
+        );
+        assert!(Tail::new("-", None).is_err());
+    }
+}
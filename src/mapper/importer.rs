@@ -0,0 +1,328 @@
+//! Import files of any format, the mapper of each file
+//! is selected by the format of its layout.
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use serde_json::Value;
+use client::Message;
+use super::sheet::Sheet;
+use super::{rules, Arrow, Csv, Format, Json, Layout, Legends, LineProtocol, Log, Mapper, Mscons,
+            Prometheus, Rule, Sink, Sql};
+use error::{ConvertError, ConvertResult};
+
+/// The options shared by the mappers of all formats.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    /// Only read the first rows of each file.
+    pub limit: Option<usize>,
+    /// Skip the rows of each file up to the given one
+    /// which were imported by some former run.
+    pub resume: HashMap<String, usize>,
+}
+
+impl Options {
+    /// The last row of some source imported by a former run.
+    pub fn resume(&self, source: &str) -> usize {
+        self.resume.get(source).cloned().unwrap_or(0)
+    }
+
+    /// The number of rows to read of each source.
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(usize::MAX)
+    }
+
+    /// Whether the sources are read up to their end.
+    pub fn complete(&self) -> bool {
+        self.limit.is_none()
+    }
+}
+
+/// Pass the rows of some source to the sink, so the mappers
+/// only parse and convert them. Rows imported by a former run
+/// are skipped and the limit counts the rows after them.
+pub struct Driver<'a> {
+    source: &'a str,
+    sink: &'a dyn Sink,
+    options: &'a Options,
+    read: usize,
+    last: usize,
+}
+
+impl<'a> Driver<'a> {
+    pub fn new(options: &'a Options, source: &'a str, sink: &'a dyn Sink) -> Driver<'a> {
+        Driver {
+            source,
+            sink,
+            options,
+            read: 0,
+            last: 0,
+        }
+    }
+
+    /// Count the rows up to the given one as read, e.g. the header.
+    pub fn skip(&mut self, row: usize) {
+        self.last = self.last.max(row);
+    }
+
+    /// Convert some row unless it was imported before and send
+    /// its points or reject it. Return false once the limit is
+    /// reached, the row and the ones after it aren't read then.
+    pub fn row<P, F>(&mut self, row: usize, convert: F) -> bool
+    where
+        P: IntoIterator<Item = ConvertResult<Message>>,
+        F: FnOnce() -> P,
+    {
+        if row <= self.options.resume(self.source) {
+            return true;
+        }
+        if self.read >= self.options.limit() {
+            return false;
+        }
+        self.read += 1;
+        self.last = row;
+        for point in convert() {
+            match point {
+                Ok(msg) => {
+                    debug!(format!("Sending: {:?}", msg));
+                    if let Err(e) = self.sink.send(msg) {
+                        error!(format!("Failed to import file: {}", e));
+                    }
+                }
+                Err(e) => self.sink.reject(self.source, row, e),
+            }
+        }
+        self.sink.progress(self.source, row, false);
+        true
+    }
+
+    /// Report the last row read, the source is done if it was
+    /// read without limit.
+    pub fn finish(self) {
+        let last = self.last.max(self.options.resume(self.source));
+        self.sink.progress(self.source, last, self.options.complete());
+    }
+}
+
+/// Dispatch files to the mapper of their format.
+///
+/// If layout rules are given the layout of each
+/// file is selected by its name. The legends of
+/// the layout are applied to the points of all formats.
+#[derive(Debug, Default)]
+pub struct Importer {
+    files: Vec<String>,
+    rules: Vec<Rule>,
+    options: Options,
+    follow: Option<Arc<AtomicBool>>,
+    last: RefCell<BTreeMap<String, Value>>,
+}
+
+impl Importer {
+    pub fn new(files: Vec<String>) -> Importer {
+        Importer {
+            files,
+            ..Importer::default()
+        }
+    }
+
+    /// Select the layout of each file with the given rules.
+    pub fn with_rules(mut self, rules: Vec<Rule>) -> Importer {
+        self.rules = rules;
+        self
+    }
+
+    /// Only read the first `rows` data rows of each file.
+    pub fn with_limit(mut self, rows: usize) -> Importer {
+        self.options.limit = Some(rows);
+        self
+    }
+
+    /// Skip the rows of each file up to the given row
+    /// which were imported by some former run.
+    pub fn with_resume(mut self, rows: HashMap<String, usize>) -> Importer {
+        self.options.resume = rows;
+        self
+    }
+
+    /// Keep reading new rows of the files until `stop` is set,
+    /// only csv files can be followed.
+    pub fn with_follow(mut self, stop: Arc<AtomicBool>) -> Importer {
+        self.follow = Some(stop);
+        self
+    }
+
+    /// Bind `:last` of the queries of sqlite databases
+    /// to the given time values, see `Sql::with_last`.
+    pub fn with_last(self, last: BTreeMap<String, Value>) -> Importer {
+        *self.last.borrow_mut() = last;
+        self
+    }
+
+    /// The greatest time value imported of each sqlite database.
+    pub fn last(&self) -> BTreeMap<String, Value> {
+        self.last.borrow().clone()
+    }
+
+    /// Return the layout for some file which is the one of the
+    /// first matching rule or the given layout if there are no
+    /// rules. Files matching no rule have no layout.
+    pub fn layout_for<'a>(&'a self, file: &str, layout: &'a Layout) -> Option<&'a Layout> {
+        match self.rules.is_empty() {
+            true => Some(layout),
+            false => rules::select(&self.rules, file),
+        }
+    }
+
+    /// Read a single file with the mapper of its format
+    /// and pass its points to the sink.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let legends = Legends::new(layout)?;
+        let sink = &legends.sink(sink);
+        let options = self.options.clone();
+        match layout.format(file) {
+            Format::Csv | Format::Fixed => Csv::new(vec![], false)
+                .with_options(options)
+                .import_file(file, layout, sink),
+            Format::Json => Json::new(options).import_file(file, layout, sink),
+            Format::Log => Log::new(options).import_file(file, layout, sink),
+            Format::Line => LineProtocol::new(options).import_file(file, layout, sink),
+            Format::Sqlite => {
+                let sql = Sql::new(options).with_last(self.last());
+                let result = sql.import_file(file, layout, sink);
+                *self.last.borrow_mut() = sql.last();
+                result
+            }
+            Format::Sheet => Sheet::new(options).import_file(file, layout, sink),
+            Format::Mscons => Mscons::new(options).import_file(file, layout, sink),
+            Format::Parquet | Format::Arrow => Arrow::new(options).import_file(file, layout, sink),
+            Format::Prometheus => Prometheus::new(options).import_file(file, layout, sink),
+        }
+    }
+
+    /// Read the csv files and their new rows until stopped.
+    fn follow(&self, layout: &Layout, sink: &dyn Sink, stop: &AtomicBool) -> ConvertResult<()> {
+        let mut files = vec![];
+        for file in &self.files {
+            let layout = match self.layout_for(file, layout) {
+                Some(layout) => layout,
+                None => {
+                    error!(format!("No layout rule matches {}", file));
+                    continue;
+                }
+            };
+            if !matches!(layout.format(file), Format::Csv | Format::Fixed) {
+                return Err(ConvertError::Config(format!("Only csv files can be followed: {}", file)));
+            }
+            files.push((file.as_str(), layout));
+        }
+        Csv::new(vec![], false).follow(&files, sink, stop)
+    }
+}
+
+impl Mapper for Importer {
+    fn import(&self, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        if let Some(ref stop) = self.follow {
+            return self.follow(layout, sink, stop);
+        }
+
+        let mut unmatched = vec![];
+        for (i, file) in self.files.iter().enumerate() {
+            info!(format!("[{}/{}] {}", i + 1, self.files.len(), file));
+            let layout = match self.layout_for(file, layout) {
+                Some(layout) => layout,
+                None => {
+                    error!(format!("No layout rule matches {}", file));
+                    unmatched.push(file.as_str());
+                    continue;
+                }
+            };
+            debug!(format!("Opening {:?} with layout {:?}", file, layout));
+            self.import_file(file, layout, sink)?;
+        }
+        if !unmatched.is_empty() {
+            error!(format!(
+                "Skipped {} files without layout rule: {}",
+                unmatched.len(),
+                unmatched.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+    use client::FieldValue;
+    use mapper::test::Collect;
+    use mapper::Legend;
+
+    #[test]
+    fn test_legend() {
+        let mut layout = Layout::load("assets/test.toml").unwrap();
+        let mut legend = Legend {
+            column: "Status".into(),
+            tag: Some("Status".into()),
+            ..Legend::default()
+        };
+        legend.values.insert("220".into(), "valid".into());
+        layout.legend.push(legend.clone());
+
+        let importer = Importer::default();
+        let sink = Collect::default();
+        importer.import_file("assets/test.csv", &layout, &sink).unwrap();
+        assert_eq!(sink.sent.borrow().len(), 4);
+        assert_eq!(sink.sent.borrow()[0].tags()[2], ("Status".into(), "valid".into()));
+
+        // only substituted values are imported
+        legend.only = vec!["67".into()];
+        layout.legend = vec![legend];
+        let sink = Collect::default();
+        importer.import_file("assets/test.csv", &layout, &sink).unwrap();
+        assert!(sink.sent.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_driver() {
+        let options = Options {
+            limit: Some(2),
+            resume: vec![("a.csv".to_string(), 1)].into_iter().collect(),
+        };
+        let sink = Collect::default();
+        let mut driver = Driver::new(&options, "a.csv", &sink);
+        let field = |v| ("v".to_string(), FieldValue::Integer(v));
+        let point = |v| Ok(Message::new("s".into(), Utc::now(), field(v), vec![]));
+        assert!(driver.row(1, || -> Option<_> { panic!("resumed rows aren't converted") }));
+        assert!(driver.row(2, || vec![point(2), point(3)]));
+        assert!(driver.row(3, || Some(Err(ConvertError::Import("broken".into())))));
+        assert!(!driver.row(4, || Some(point(4))));
+        driver.finish();
+        assert_eq!(sink.sent.borrow().len(), 2);
+        assert_eq!(*sink.rejected.borrow(), vec![3]);
+    }
+
+    #[test]
+    fn test_layout_for_file() {
+        let layout = Layout::default();
+        let importer = Importer::new(vec![]);
+        assert_eq!(importer.layout_for("drop/a.txt", &layout), Some(&layout));
+
+        let pv = Layout {
+            series: "pv".into(),
+            ..Layout::default()
+        };
+        let importer = importer.with_rules(vec![Rule::glob("pv_*.csv", pv.clone()).unwrap()]);
+        assert_eq!(importer.layout_for("drop/pv_1.csv", &layout), Some(&pv));
+        assert_eq!(importer.layout_for("drop/a.txt", &layout), None);
+    }
+
+    #[test]
+    fn test_follow_csv_only() {
+        let importer = Importer::new(vec!["assets/test.xlsx".into()])
+            .with_follow(Arc::new(AtomicBool::new(true)));
+        assert!(importer.import(&Layout::default(), &Collect::default()).is_err());
+    }
+}
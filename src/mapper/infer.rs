@@ -0,0 +1,253 @@
+//! Propose a layout for some sample of csv lines.
+//!
+//! The delimiter and header row are found by looking for
+//! a block of rows at the end of the sample which share the
+//! same number of columns and contain typed values. The header
+//! is the textual row right above this block. Each column is
+//! then classified as time, numeric or categorical.
+use std::fmt;
+use std::io::BufRead;
+
+use chrono::NaiveDateTime;
+use super::{source, FieldType, Layout};
+use error::{ConvertError, ConvertResult};
+
+/// Delimiters which are tried in this order.
+const DELIMITERS: &[char] = &[',', ';', '\t', '|'];
+
+/// Timestamp formats which are tried in this order.
+/// Day first formats are preferred over month first ones.
+const FORMATS: &[&str] = &[
+    "%F %H:%M:%S",
+    "%F %H:%M",
+    "%FT%H:%M:%S",
+    "%FT%H:%M:%SZ",
+    "%F %H:%M:%S%.f",
+    "%FT%H:%M:%S%.f",
+    "%FT%H:%M:%S%.fZ",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y/%m/%d %H:%M",
+];
+
+/// How some column is used in the proposed layout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Class {
+    /// A timestamp in the given format.
+    Time(String),
+    /// Measurement values of some type.
+    Numeric(FieldType),
+    /// Values which are better used as tags.
+    Categorical,
+    /// A column without any value in the sample.
+    Empty,
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Class::Time(ref format) => write!(f, "time ({})", format),
+            Class::Numeric(t) => write!(f, "numeric ({})", t.name()),
+            Class::Categorical => write!(f, "categorical"),
+            Class::Empty => write!(f, "empty"),
+        }
+    }
+}
+
+/// The proposed layout with the classification of all columns.
+#[derive(Debug, Clone)]
+pub struct Inference {
+    pub layout: Layout,
+    pub columns: Vec<(String, Class)>,
+}
+
+/// Whether some cell contains a number.
+fn is_number(cell: &str) -> bool {
+    cell.parse::<f64>().is_ok()
+}
+
+/// Whether some cell contains a timestamp of a known format.
+fn is_time(cell: &str) -> bool {
+    FORMATS
+        .iter()
+        .any(|f| NaiveDateTime::parse_from_str(cell, f).is_ok())
+}
+
+/// Split a line and trim the cells.
+fn split(line: &str, delimiter: char) -> Vec<&str> {
+    line.split(delimiter).map(|c| c.trim()).collect()
+}
+
+/// Return the header row index and number of columns for some
+/// delimiter or none if the sample doesn't look like csv.
+fn find_header(lines: &[String], delimiter: char) -> Option<(usize, usize, usize)> {
+    let last = lines.iter().rposition(|l| !l.trim().is_empty())?;
+    let columns = split(&lines[last], delimiter).len();
+    if columns < 2 {
+        return None;
+    }
+
+    // walk up while the rows look like data rows
+    let mut first = last + 1;
+    while first > 0 {
+        let row = split(&lines[first - 1], delimiter);
+        let typed = row.iter().any(|c| is_number(c) || is_time(c));
+        if row.len() != columns || !typed {
+            break;
+        }
+        first -= 1;
+    }
+
+    let header = first.checked_sub(1)?;
+    let row = split(&lines[header], delimiter);
+    let textual = row.iter().all(|c| !c.is_empty() && !is_number(c));
+    match textual && first <= last {
+        true => Some((header, columns, last + 1 - first)),
+        false => None,
+    }
+}
+
+/// Classify the values of a single column.
+fn classify(values: &[&str]) -> Class {
+    let values: Vec<&str> = values.iter().cloned().filter(|v| !v.is_empty()).collect();
+    if values.is_empty() {
+        return Class::Empty;
+    }
+
+    let time = FORMATS.iter().find(|f| {
+        values
+            .iter()
+            .all(|v| NaiveDateTime::parse_from_str(v, f).is_ok())
+    });
+    if let Some(f) = time {
+        return Class::Time(f.to_string());
+    }
+
+    let parses = |t: FieldType| values.iter().all(|v| t.parse(v).is_ok());
+    if parses(FieldType::Integer) {
+        Class::Numeric(FieldType::Integer)
+    } else if parses(FieldType::Float) {
+        Class::Numeric(FieldType::Float)
+    } else {
+        Class::Categorical
+    }
+}
+
+/// Read the first lines of some file with the given or detected encoding.
+pub fn sample(file: &str, rows: usize, encoding: Option<&str>) -> ConvertResult<Vec<String>> {
+    source::open(file, encoding)?
+        .lines()
+        .take(rows)
+        .collect::<Result<_, _>>()
+        .map_err(ConvertError::from)
+}
+
+/// Propose a layout for the sample lines of some csv file.
+pub fn infer(lines: &[String]) -> ConvertResult<Inference> {
+    let (delimiter, (header, columns, _)) = DELIMITERS
+        .iter()
+        .filter_map(|&d| find_header(lines, d).map(|h| (d, h)))
+        .max_by_key(|&(_, (_, columns, rows))| (rows, columns))
+        .ok_or_else(|| ConvertError::Import("No header row with data rows found".into()))?;
+
+    let names = split(&lines[header], delimiter);
+    let rows: Vec<Vec<&str>> = lines[header + 1..]
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| split(l, delimiter))
+        .filter(|r| r.len() == columns)
+        .collect();
+
+    let classes: Vec<(String, Class)> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let values: Vec<&str> = rows.iter().map(|r| r[i]).collect();
+            (name.to_string(), classify(&values))
+        })
+        .collect();
+
+    let mut layout = Layout {
+        delimiter,
+        skip_rows: header,
+        tags: vec![],
+        ..Layout::default()
+    };
+
+    let time = classes.iter().find(|(_, c)| matches!(*c, Class::Time(_)));
+    if let Some((name, Class::Time(format))) = time {
+        layout.time = name.clone();
+        layout.tformat = format.clone();
+    }
+
+    for (name, class) in &classes {
+        match *class {
+            Class::Numeric(t) => {
+                if layout.types.is_empty() {
+                    layout.measure = name.clone();
+                }
+                layout.types.insert(name.clone(), t);
+            }
+            Class::Categorical => layout.tags.push(name.clone()),
+            _ => (),
+        }
+    }
+
+    Ok(Inference {
+        layout,
+        columns: classes,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_infer_sample() {
+        let inference = infer(&sample("assets/test.csv", 100, None).unwrap()).unwrap();
+        let layout = inference.layout;
+
+        assert_eq!(layout.delimiter, ';');
+        assert_eq!(layout.skip_rows, 10);
+        assert_eq!(layout.time, "timestamp");
+        assert_eq!(layout.tformat, "%d.%m.%Y %H:%M");
+        assert_eq!(layout.measure, "Profilwert kWh");
+        assert_eq!(layout.field_type("Profilwert kWh"), FieldType::Float);
+        assert_eq!(layout.field_type("Status"), FieldType::Integer);
+        assert!(layout.tags.is_empty());
+    }
+
+    #[test]
+    fn test_infer_tags() {
+        let sample = lines(
+            "time,site,power,ok\n\
+             2016-01-01 00:00:00,north,1.5,true\n\
+             2016-01-01 00:15:00,south,2,false\n",
+        );
+        let inference = infer(&sample).unwrap();
+        assert_eq!(inference.layout.delimiter, ',');
+        assert_eq!(inference.layout.skip_rows, 0);
+        assert_eq!(inference.layout.time, "time");
+        assert_eq!(inference.layout.tformat, "%F %H:%M:%S");
+        assert_eq!(inference.layout.measure, "power");
+        assert_eq!(inference.layout.tags, vec!["site", "ok"]);
+        assert_eq!(inference.columns[1].1, Class::Categorical);
+    }
+
+    #[test]
+    fn test_no_header() {
+        assert!(infer(&lines("a\nb\nc")).is_err());
+        assert!(infer(&lines("1,2\n3,4")).is_err());
+        assert!(infer(&[]).is_err());
+    }
+}
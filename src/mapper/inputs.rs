@@ -0,0 +1,227 @@
+//! Expand the inputs given on the command line into files.
+//! Directories are listed, optionally with all subdirectories,
+//! and glob patterns are expanded without the shell, so large
+//! archives don't hit the argument limit. The files found are
+//! filtered by include and exclude globs and sorted by name
+//! or modification time.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use glob::{self, Pattern};
+use error::{ConvertError, ConvertResult};
+
+/// The order of the files found for some input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    Name,
+    Mtime,
+}
+
+impl Order {
+    /// Parse an order from its name as used on the command line.
+    pub fn from_name(name: &str) -> ConvertResult<Order> {
+        match name.trim() {
+            "name" => Ok(Order::Name),
+            "mtime" => Ok(Order::Mtime),
+            n => Err(ConvertError::Config(format!("Unknown file order {}", n))),
+        }
+    }
+}
+
+/// Settings to expand inputs into files.
+#[derive(Debug, Clone)]
+pub struct Inputs {
+    recursive: bool,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    order: Order,
+}
+
+impl Default for Inputs {
+    fn default() -> Self {
+        Inputs {
+            recursive: false,
+            include: vec![],
+            exclude: vec![],
+            order: Order::Name,
+        }
+    }
+}
+
+/// Parse some globs or return a configuration error.
+fn patterns<S: AsRef<str>>(globs: &[S]) -> ConvertResult<Vec<Pattern>> {
+    globs
+        .iter()
+        .map(|g| g.as_ref())
+        .map(|g| {
+            Pattern::new(g).map_err(|e| ConvertError::Config(format!("Invalid glob {}: {}", g, e)))
+        })
+        .collect()
+}
+
+/// Whether a glob matches the file. Globs without a path
+/// separator are matched against the file name only.
+fn matches(pattern: &Pattern, file: &Path) -> bool {
+    match pattern.as_str().contains('/') {
+        true => pattern.matches_path(file),
+        false => file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| pattern.matches(n)),
+    }
+}
+
+/// Whether the input is a glob pattern instead of a path.
+fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '[']) && !Path::new(input).exists()
+}
+
+impl Inputs {
+    /// Read the files of subdirectories as well.
+    pub fn with_recursive(mut self, recursive: bool) -> Inputs {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Only use files found in directories or by globs
+    /// which match one of the globs.
+    pub fn with_include<S: AsRef<str>>(mut self, globs: &[S]) -> ConvertResult<Inputs> {
+        self.include = patterns(globs)?;
+        Ok(self)
+    }
+
+    /// Skip files found in directories or by globs
+    /// which match one of the globs.
+    pub fn with_exclude<S: AsRef<str>>(mut self, globs: &[S]) -> ConvertResult<Inputs> {
+        self.exclude = patterns(globs)?;
+        Ok(self)
+    }
+
+    /// Sort the files found for each input.
+    pub fn with_order(mut self, order: Order) -> Inputs {
+        self.order = order;
+        self
+    }
+
+    /// Whether a found file passes the include and exclude globs.
+    fn accepts(&self, file: &Path) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| matches(p, file)))
+            && !self.exclude.iter().any(|p| matches(p, file))
+    }
+
+    /// Collect the files of some directory.
+    fn walk(&self, dir: &Path, files: &mut Vec<PathBuf>) -> ConvertResult<()> {
+        let entries = fs::read_dir(dir).map_err(|e| {
+            ConvertError::Import(format!("Failed to read directory {}: {}", dir.display(), e))
+        })?;
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            // symlinked directories are not followed to avoid loops
+            if entry.file_type()?.is_dir() {
+                if self.recursive {
+                    self.walk(&path, files)?;
+                }
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sort the files by the configured order, ties by name.
+    fn sort(&self, files: &mut [PathBuf]) {
+        match self.order {
+            Order::Name => files.sort(),
+            Order::Mtime => files.sort_by_cached_key(|f| {
+                let mtime = fs::metadata(f).and_then(|m| m.modified());
+                (mtime.unwrap_or(SystemTime::UNIX_EPOCH), f.clone())
+            }),
+        }
+    }
+
+    /// Expand the inputs into files. Plain files and `-` for stdin
+    /// are kept as given, directories and globs are replaced by
+    /// the files they contain in order.
+    pub fn expand(&self, inputs: &[String]) -> ConvertResult<Vec<String>> {
+        let mut result = vec![];
+        for input in inputs {
+            let mut found = vec![];
+            if input == "-" || Path::new(input).is_file() {
+                result.push(input.clone());
+                continue;
+            } else if Path::new(input).is_dir() {
+                self.walk(Path::new(input), &mut found)?;
+            } else if is_glob(input) {
+                let paths = glob::glob(input).map_err(|e| {
+                    ConvertError::Config(format!("Invalid glob {}: {}", input, e))
+                })?;
+                found.extend(paths.filter_map(|p| p.ok()).filter(|p| p.is_file()));
+            } else {
+                // let the mapper report the missing file
+                result.push(input.clone());
+                continue;
+            }
+
+            found.retain(|f| self.accepts(f));
+            self.sort(&mut found);
+            debug!(format!("Found {} files for {}", found.len(), input));
+            result.extend(found.iter().map(|f| f.to_string_lossy().into_owned()));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mapper::test::temp;
+    use std::fs::File;
+
+    fn tree(name: &str) -> PathBuf {
+        let dir = temp(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for f in &["b.csv", "a.csv", "notes.txt", "sub/c.csv"] {
+            File::create(dir.join(f)).unwrap();
+        }
+        dir
+    }
+
+    fn names(files: &[String]) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| Path::new(f).file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_directory() {
+        let dir = tree("inputs-dir");
+        let input = vec![dir.to_string_lossy().into_owned()];
+
+        let files = Inputs::default().expand(&input).unwrap();
+        assert_eq!(names(&files), vec!["a.csv", "b.csv", "notes.txt"]);
+
+        let inputs = Inputs::default()
+            .with_recursive(true)
+            .with_include(&["*.csv"])
+            .unwrap()
+            .with_exclude(&["b.*"])
+            .unwrap();
+        assert_eq!(names(&inputs.expand(&input).unwrap()), vec!["a.csv", "c.csv"]);
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let dir = tree("inputs-glob");
+        let glob = format!("{}/*.csv", dir.display());
+        let files = Inputs::default()
+            .expand(&[glob, "-".into(), "missing.csv".into()])
+            .unwrap();
+        assert_eq!(names(&files), vec!["a.csv", "b.csv", "-", "missing.csv"]);
+        assert!(Inputs::default().with_include(&["[a"]).is_err());
+        assert!(Order::from_name("size").is_err());
+    }
+}
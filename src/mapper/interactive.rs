@@ -4,7 +4,6 @@
 use super::*;
 
 use std::io::{self, Write};
-use chrono::{TimeZone, Utc};
 use client::Message;
 
 /// Stub struct for satisfying the trait
@@ -38,7 +37,7 @@ impl Interactive {
 /// The interactive mode allows to provide all needed
 /// input data by hand.
 impl Mapper for Interactive {
-    fn import(&self, layout: &Layout, client: &InfluxClient) -> ConvertResult<()> {
+    fn import(&self, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
         println!("Interactive mode...");
         println!("Insert tags comma separated.\nExit with C-d");
 
@@ -51,7 +50,7 @@ impl Mapper for Interactive {
                 }
             };
 
-            let time = match Utc.datetime_from_str(&time, &layout.tformat) {
+            let time = match layout.parse_time(&time) {
                 Ok(t) => t,
                 Err(e) => {
                     error!(format!("Parsing time failed: {}", e));
@@ -59,6 +58,14 @@ impl Mapper for Interactive {
                 }
             };
 
+            let value = match layout.parse_value(&layout.measure, &measure) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(format!("Parsing measurement failed: {}", e));
+                    continue;
+                }
+            };
+
             let tags = layout
                 .tags
                 .iter()
@@ -67,9 +74,14 @@ impl Mapper for Interactive {
                 .zip(tags)
                 .collect();
 
-            debug!(format!("{},{},{:?}", measure, time, tags));
-            let msg = Message::new(time, (layout.measure.clone(), measure), tags);
-            if let Err(e) = client.send(msg) {
+            debug!(format!("{},{},{:?}", value, time, tags));
+            let msg = Message::new(
+                layout.series.clone(),
+                time,
+                (layout.measure.clone(), value),
+                tags,
+            );
+            if let Err(e) = sink.send(msg) {
                 error!(format!("Sending to background client failed: {}", e));
             }
         }
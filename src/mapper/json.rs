@@ -0,0 +1,480 @@
+//! Import json documents with an array of records
+//! or newline delimited json with one record per line.
+//!
+//! The measure, time and tags of the layout are paths
+//! into each record, either dotted like `$.readings[*].value`
+//! or json pointers like `/readings/*/value`. A path with
+//! `[*]` selects every element of an array and one point is
+//! created for each element. Time and tags are taken from the
+//! same element or its parents, the name of a field or tag is
+//! the last key of its path.
+//!
+//! ```toml
+//! format = "json"
+//! measure = "$.readings[*].value"
+//! time = "$.readings[*].ts"
+//! tags = ["$.meter"]
+//! tformat = "%s"
+//! ```
+use std::fmt;
+use std::io::BufRead;
+
+use super::source;
+use super::{Driver, Layout, Options, Sink};
+use client::{FieldValue, Message};
+use error::{ConvertError, ConvertResult};
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use serde_json::{self, Value};
+
+/// A single step of some path.
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    All,
+}
+
+/// A path selecting values of a json record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    raw: String,
+    steps: Vec<Step>,
+}
+
+/// The values selected by some path with the
+/// positions of the arrays passed by `[*]`.
+type Found<'a> = Vec<(Vec<usize>, &'a Value)>;
+
+impl Path {
+    /// Parse a dotted path like `$.a.b[*].c` or `a.b` or
+    /// a json pointer like `/a/b/*/c`.
+    pub fn parse(path: &str) -> ConvertResult<Path> {
+        let err = |m: &str| ConvertError::Config(format!("Invalid path {}: {}", path, m));
+        let mut steps = vec![];
+
+        if let Some(pointer) = path.strip_prefix('/') {
+            for token in pointer.split('/') {
+                steps.push(match token {
+                    "*" => Step::All,
+                    t => Step::Key(t.replace("~1", "/").replace("~0", "~")),
+                });
+            }
+            return Ok(Path {
+                raw: path.into(),
+                steps,
+            });
+        }
+
+        let rest = path.strip_prefix('$').unwrap_or(path);
+        let mut chars = rest.chars();
+        let mut key = String::new();
+        let mut dotted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '.' | '[' => {
+                    if !key.is_empty() {
+                        steps.push(Step::Key(key.clone()));
+                        key.clear();
+                    } else if dotted && c == '.' {
+                        return Err(err("empty key"));
+                    }
+                    dotted = c == '.';
+                    if c == '[' {
+                        let inner: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                        let inner = inner.trim();
+                        steps.push(match inner {
+                            "*" => Step::All,
+                            i if i.starts_with(['\'', '"']) && i.len() > 1 => {
+                                Step::Key(i[1..i.len() - 1].into())
+                            }
+                            i => Step::Index(i.parse().map_err(|_| err("expected index"))?),
+                        });
+                    }
+                }
+                c => {
+                    key.push(c);
+                    dotted = false;
+                }
+            }
+        }
+        if !key.is_empty() {
+            steps.push(Step::Key(key));
+        } else if dotted {
+            return Err(err("empty key"));
+        }
+        Ok(Path {
+            raw: path.into(),
+            steps,
+        })
+    }
+
+    /// The name of the selected value, which is the last key of the path.
+    pub fn name(&self) -> String {
+        self.steps
+            .iter()
+            .rev()
+            .find_map(|s| match *s {
+                Step::Key(ref k) => Some(k.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.raw.clone())
+    }
+
+    /// Return all values selected by the path.
+    fn select<'a>(&self, value: &'a Value) -> Found<'a> {
+        let mut found = vec![(vec![], value)];
+        for step in &self.steps {
+            let mut next = vec![];
+            for (positions, value) in found {
+                match (step, value) {
+                    (Step::All, Value::Array(a)) => {
+                        for (i, v) in a.iter().enumerate() {
+                            let mut p = positions.clone();
+                            p.push(i);
+                            next.push((p, v));
+                        }
+                    }
+                    (Step::All, Value::Object(o)) => {
+                        next.extend(o.values().map(|v| (positions.clone(), v)))
+                    }
+                    (Step::Index(i), Value::Array(a)) => {
+                        next.extend(a.get(*i).map(|v| (positions, v)))
+                    }
+                    (Step::Key(k), Value::Object(o)) => {
+                        next.extend(o.get(k).map(|v| (positions, v)))
+                    }
+                    // pointers don't distinguish keys and indices
+                    (Step::Key(k), Value::Array(a)) => next.extend(
+                        k.parse::<usize>()
+                            .ok()
+                            .and_then(|i| a.get(i))
+                            .map(|v| (positions, v)),
+                    ),
+                    _ => (),
+                }
+            }
+            found = next;
+        }
+        found
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// Return the value found within the same array element
+/// as `positions` or one of its parents.
+fn lookup<'a>(found: &Found<'a>, positions: &[usize]) -> Option<&'a Value> {
+    found
+        .iter()
+        .find(|(p, _)| positions.starts_with(p) || p.starts_with(positions))
+        .map(|(_, v)| *v)
+}
+
+/// The raw text of some value, strings without quotes.
+fn text(value: &Value) -> String {
+    match *value {
+        Value::String(ref s) => s.clone(),
+        ref v => v.to_string(),
+    }
+}
+
+/// The paths of a layout.
+struct Paths {
+    measure: Path,
+    time: Path,
+    tags: Vec<Path>,
+}
+
+impl Paths {
+    fn new(layout: &Layout) -> ConvertResult<Paths> {
+        Ok(Paths {
+            measure: Path::parse(&layout.measure)?,
+            time: Path::parse(&layout.time)?,
+            tags: layout
+                .tags
+                .iter()
+                .map(|t| Path::parse(t))
+                .collect::<ConvertResult<_>>()?,
+        })
+    }
+
+    /// Convert the value into a field. Numbers are floats and
+    /// the other values keep their type unless the layout
+    /// configures a type for the measure.
+    fn field(&self, layout: &Layout, value: &Value) -> ConvertResult<FieldValue> {
+        if layout.types.contains_key(&layout.measure) {
+            return layout.parse_value(&layout.measure, &text(value));
+        }
+        match *value {
+            Value::Number(ref n) => Ok(FieldValue::Float(n.as_f64().unwrap_or_default())),
+            Value::Bool(b) => Ok(FieldValue::Boolean(b)),
+            Value::String(ref s) => Ok(FieldValue::String(s.clone())),
+            ref v => Err(ConvertError::Import(format!(
+                "Value {} of {} is no field value",
+                v, self.measure
+            ))),
+        }
+    }
+
+    /// Convert a record into one point for each measure found.
+    fn convert(&self, layout: &Layout, record: &Value) -> Vec<ConvertResult<Message>> {
+        let measures = self.measure.select(record);
+        if measures.is_empty() {
+            let err = format!("No value found for {}", self.measure);
+            return vec![Err(ConvertError::Import(err))];
+        }
+        let times = self.time.select(record);
+        let tags: Vec<(String, Found)> = self
+            .tags
+            .iter()
+            .map(|t| (t.name(), t.select(record)))
+            .collect();
+
+        let point = |positions: &[usize], value: &Value| {
+            let value = self.field(layout, value)?;
+            let time = lookup(&times, positions)
+                .ok_or_else(|| ConvertError::Import(format!("No time found for {}", self.time)))?;
+            let timestamp = layout.parse_time(&text(time))?;
+            // missing tags are left out like missing tag columns
+            let tags = tags
+                .iter()
+                .filter_map(|(name, found)| {
+                    lookup(found, positions).map(|v| (name.clone(), text(v)))
+                })
+                .collect();
+            Ok(Message::new(
+                layout.series.clone(),
+                timestamp,
+                (self.measure.name(), value),
+                tags,
+            ))
+        };
+        measures.iter().map(|(p, v)| point(p, v)).collect()
+    }
+}
+
+/// Pass each element of a json array to the callback
+/// without reading the whole array into memory.
+struct Elements<'f, F: 'f>(&'f mut F);
+
+impl<'de, 'f, F: FnMut(Value)> Visitor<'de> for Elements<'f, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of records")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(value) = seq.next_element()? {
+            (self.0)(value);
+        }
+        Ok(())
+    }
+}
+
+/// Skip leading whitespace and return whether the input is an array.
+fn is_array<R: BufRead>(reader: &mut R) -> ConvertResult<bool> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(p) => {
+                let array = buf[p] == b'[';
+                reader.consume(p);
+                return Ok(array);
+            }
+            None => {
+                let n = buf.len();
+                reader.consume(n);
+            }
+        }
+    }
+}
+
+/// A convenient struct to import json files.
+///
+/// Each element of a top level array or each line of
+/// newline delimited json is a record which is converted
+/// with the paths of the layout.
+#[derive(Debug, Default)]
+pub struct Json {
+    options: Options,
+}
+
+impl Json {
+    pub fn new(options: Options) -> Json {
+        Json { options }
+    }
+
+    /// The layout with the paths replaced by the names of the
+    /// fields and tags, e.g. to show the points.
+    pub fn named(layout: &Layout) -> Layout {
+        let name = |p: &str| Path::parse(p).map(|p| p.name()).unwrap_or_else(|_| p.into());
+        Layout {
+            measure: name(&layout.measure),
+            time: name(&layout.time),
+            tags: layout.tags.iter().map(|t| name(t)).collect(),
+            ..layout.clone()
+        }
+    }
+
+    /// Read a single file and pass its records to the sink.
+    /// The file `-` is read from stdin.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let reader = source::open(file, layout.encoding.as_deref())?;
+        self.import_reader(file, reader, layout, sink)
+    }
+
+    /// Read json from some reader and pass its records to the sink.
+    /// Records are counted from 1, for newline delimited json
+    /// this is the line number.
+    pub fn import_reader<R: BufRead>(
+        &self,
+        source: &str,
+        mut reader: R,
+        layout: &Layout,
+        sink: &dyn Sink,
+    ) -> ConvertResult<()> {
+        let paths = Paths::new(layout)?;
+        let mut driver = Driver::new(&self.options, source, sink);
+        let mut row = 0;
+
+        let mut record = |row: usize, value: ConvertResult<Value>| {
+            driver.row(row, || match value {
+                Ok(v) => paths.convert(layout, &v),
+                Err(e) => vec![Err(e)],
+            })
+        };
+
+        if is_array(&mut reader)? {
+            let mut de = serde_json::Deserializer::from_reader(reader);
+            let result = de.deserialize_seq(Elements(&mut |v| {
+                row += 1;
+                record(row, Ok(v));
+            }));
+            // the records after some syntax error can't be read
+            if let Err(e) = result {
+                record(row + 1, Err(ConvertError::Import(format!("{}", e))));
+            }
+        } else {
+            for (i, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let value =
+                    serde_json::from_str(&line).map_err(|e| ConvertError::Import(format!("{}", e)));
+                if !record(i + 1, value) {
+                    break;
+                }
+            }
+        }
+
+        driver.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mapper::test::Collect;
+
+    fn layout() -> Layout {
+        Layout {
+            measure: "$.readings[*].value".into(),
+            time: "$.readings[*].ts".into(),
+            tags: vec!["$.meter".into(), "/readings/*/status".into()],
+            tformat: "%s".into(),
+            ..Layout::default()
+        }
+    }
+
+    #[test]
+    fn test_path() {
+        let record: Value =
+            serde_json::from_str(r#"{"meter": "m1", "a": {"b": [{"c": 1}, {"c": 2}], "x.y": 3}}"#)
+                .unwrap();
+        let values = |p: &str| -> Vec<Value> {
+            Path::parse(p)
+                .unwrap()
+                .select(&record)
+                .into_iter()
+                .map(|(_, v)| v.clone())
+                .collect()
+        };
+        assert_eq!(values("$.a.b[*].c"), vec![Value::from(1), Value::from(2)]);
+        assert_eq!(values("a.b[1].c"), vec![Value::from(2)]);
+        assert_eq!(values("/a/b/0/c"), vec![Value::from(1)]);
+        assert_eq!(values("$.a['x.y']"), vec![Value::from(3)]);
+        assert!(values("$.missing").is_empty());
+        assert_eq!(Path::parse("$.a.b[*].c").unwrap().name(), "c");
+        assert!(Path::parse("$.a..b").is_err());
+        assert!(Path::parse("$.a[x]").is_err());
+        assert_eq!(Json::named(&layout()).tags, vec!["meter", "status"]);
+    }
+
+    #[test]
+    fn test_array() {
+        let data = r#"[
+            {"meter": "m1", "readings": [
+                {"ts": 0, "value": 1.5, "status": "ok"},
+                {"ts": 900, "value": 2}
+            ]},
+            {"meter": "m2", "readings": [{"ts": "x", "value": 3}]},
+            {"meter": "m3"}
+        ]"#;
+        let sink = Collect::default();
+        Json::default()
+            .import_reader("memory", data.as_bytes(), &layout(), &sink)
+            .unwrap();
+
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            sent[0].value(),
+            &("value".to_string(), FieldValue::Float(1.5))
+        );
+        assert_eq!(
+            sent[0].tags(),
+            &vec![
+                ("meter".to_string(), "m1".to_string()),
+                ("status".to_string(), "ok".to_string())
+            ]
+        );
+        assert_eq!(sent[1].time().timestamp(), 900);
+        assert_eq!(sent[1].tags().len(), 1);
+        assert_eq!(*sink.rejected.borrow(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let data = "{\"meter\": \"m1\", \"readings\": [{\"ts\": 0, \"value\": 1}]}\n\
+                    \n\
+                    {broken\n\
+                    {\"meter\": \"m2\", \"readings\": [{\"ts\": 0, \"value\": 2}]}\n";
+        let mut layout = layout();
+        layout
+            .types
+            .insert(layout.measure.clone(), ::mapper::FieldType::Integer);
+        let options = Options {
+            resume: vec![("memory".to_string(), 1)].into_iter().collect(),
+            ..Options::default()
+        };
+        let sink = Collect::default();
+        Json::new(options)
+            .import_reader("memory", data.as_bytes(), &layout, &sink)
+            .unwrap();
+
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].value().1, FieldValue::Integer(2));
+        assert_eq!(*sink.rejected.borrow(), vec![3]);
+    }
+}
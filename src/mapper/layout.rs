@@ -0,0 +1,615 @@
+//! The layout of some import source and how it is
+//! mapped onto influxdb fields. A layout can be stored
+//! in a toml file to be reused for recurring imports.
+//!
+//! ```toml
+//! series = "meter"
+//! measure = "Profilwert kWh"
+//! tags = ["Status"]
+//! time = "timestamp"
+//! tformat = "%d.%m.%Y %H:%M"
+//! timezone = "CET"
+//! delimiter = ";"
+//! skip_rows = 10
+//!
+//! [types]
+//! "Profilwert kWh" = "float"
+//!
+//! [[preamble]]
+//! tag = "meter"
+//! row = 0
+//! column = 0
+//! ```
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use toml;
+use client::FieldValue;
+use super::legend::Legend;
+use super::preamble::Extract;
+use super::fixed::{self, Column};
+use super::{log, source};
+use error::{ConvertError, ConvertResult};
+
+/// A layout describes the names for the database
+/// fields used by influx and how the source is read.
+/// See https://docs.rs/chrono/0.4.0/chrono/format/strftime/index.html
+/// for time formatting.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    /// Format of the input, detected by the file extension if not set.
+    pub format: Option<Format>,
+    /// Name of the measurement series.
+    pub series: String,
+    pub measure: String,
+    pub tags: Vec<String>,
+    pub time: String,
+    pub tformat: String,
+    /// Timezone of the timestamps, UTC if not set.
+    pub timezone: Option<String>,
+    /// Field types by column name, string if not set.
+    pub types: BTreeMap<String, FieldType>,
+    pub delimiter: char,
+    /// Encoding of the source, detected if not set.
+    pub encoding: Option<String>,
+    /// Number of rows before the header row.
+    pub skip_rows: usize,
+    /// Decimal separator of numeric cells.
+    pub decimal_sep: char,
+    /// Thousands separator of numeric cells, none if not set.
+    pub thousands_sep: Option<char>,
+    /// Settings of sqlite databases.
+    #[serde(skip_serializing_if = "is_default")]
+    pub sqlite: SqliteLayout,
+    /// Settings of log files.
+    #[serde(skip_serializing_if = "is_default")]
+    pub log: LogLayout,
+    /// Settings of line protocol files.
+    #[serde(skip_serializing_if = "is_default")]
+    pub line: LineLayout,
+    /// Settings of fixed width files.
+    #[serde(skip_serializing_if = "is_default")]
+    pub fixed: FixedLayout,
+    /// Settings of workbooks.
+    #[serde(skip_serializing_if = "is_default")]
+    pub sheet: SheetLayout,
+    /// Settings of prometheus metrics.
+    #[serde(skip_serializing_if = "is_default")]
+    pub prometheus: PrometheusLayout,
+    /// Values taken from the skipped rows.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub preamble: Vec<Extract>,
+    /// Lookup tables for the values of tags.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub legend: Vec<Legend>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            format: None,
+            series: String::from("series"),
+            measure: String::from("data"),
+            tags: [].to_vec(),
+            time: String::from("timestamp"),
+            tformat: String::from("%F %H:%M:%S"),
+            timezone: None,
+            types: BTreeMap::new(),
+            delimiter: ',',
+            encoding: None,
+            skip_rows: 0,
+            decimal_sep: '.',
+            thousands_sep: None,
+            sqlite: SqliteLayout::default(),
+            log: LogLayout::default(),
+            line: LineLayout::default(),
+            fixed: FixedLayout::default(),
+            sheet: SheetLayout::default(),
+            prometheus: PrometheusLayout::default(),
+            preamble: vec![],
+            legend: vec![],
+        }
+    }
+}
+
+impl Layout {
+    /// Read a layout from some toml file.
+    pub fn load<P: AsRef<Path>>(path: P) -> ConvertResult<Layout> {
+        let path = path.as_ref();
+        let mut content = String::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut content))
+            .map_err(|e| {
+                ConvertError::Config(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+        let layout: Layout = toml::from_str(&content)
+            .map_err(|e| ConvertError::Config(format!("{}: {}", path.display(), e)))?;
+        layout.check()?;
+        Ok(layout.relative_to(path.parent().unwrap_or(Path::new(""))))
+    }
+
+    /// Resolve the files referenced by the layout relative to some
+    /// directory, e.g. the one of the layout file.
+    pub fn relative_to(mut self, dir: &Path) -> Layout {
+        for file in self.legend.iter_mut().filter_map(|l| l.file.as_mut()) {
+            *file = dir.join(&*file).to_string_lossy().into_owned();
+        }
+        self
+    }
+
+    /// Return an error if some setting of the layout is not usable.
+    pub fn check(&self) -> ConvertResult<()> {
+        self.timezone()?;
+        source::encoding(self.encoding.as_deref())?;
+        if Some(self.decimal_sep) == self.thousands_sep {
+            return Err(ConvertError::Config(format!(
+                "Decimal and thousands separator are both {}",
+                self.decimal_sep
+            )));
+        }
+        for extract in &self.preamble {
+            extract.check()?;
+        }
+        for legend in &self.legend {
+            legend.check(&self.tags)?;
+        }
+        // the settings of other formats are never used
+        let fixed = match self.format {
+            Some(f) => f == Format::Fixed,
+            None => !self.fixed.columns.is_empty(),
+        };
+        if fixed {
+            fixed::check(&self.fixed.columns)?;
+        }
+        if matches!(self.format, None | Some(Format::Log)) && self.log.pattern.is_some() {
+            log::compile(self)?;
+        }
+        Ok(())
+    }
+
+    /// The format of some file, csv if neither the layout
+    /// nor the file extension tell.
+    pub fn format(&self, file: &str) -> Format {
+        self.format
+            .or_else(|| Format::from_extension(file))
+            .unwrap_or(Format::Csv)
+    }
+
+    /// Split some line into its cells, either by the delimiter
+    /// or by the columns of fixed width files.
+    pub fn split(&self, line: &str) -> Vec<String> {
+        match self.format {
+            Some(Format::Fixed) => fixed::split(&self.fixed.columns, line),
+            _ => line.split(self.delimiter).map(|e| e.into()).collect(),
+        }
+    }
+
+    /// Split the header line, named fixed width columns
+    /// replace the cells of the header.
+    pub fn split_header(&self, line: &str) -> Vec<String> {
+        match self.format {
+            Some(Format::Fixed) => fixed::header(&self.fixed.columns, line),
+            _ => self.split(line),
+        }
+    }
+
+    /// Use the decimal and thousands separator of some locale.
+    pub fn set_locale(&mut self, locale: &str) -> ConvertResult<()> {
+        let (decimal, thousands) = separators(locale)?;
+        self.decimal_sep = decimal;
+        self.thousands_sep = thousands;
+        Ok(())
+    }
+
+    /// Write the layout as toml file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ConvertResult<()> {
+        let content =
+            toml::to_string(self).map_err(|e| ConvertError::Config(format!("{}", e)))?;
+        File::create(path)
+            .and_then(|mut f| f.write_all(content.as_bytes()))
+            .map_err(ConvertError::from)
+    }
+
+    /// Return the configured timezone or an error if it is unknown.
+    pub fn timezone(&self) -> ConvertResult<Option<Tz>> {
+        match self.timezone {
+            None => Ok(None),
+            Some(ref tz) => tz.parse::<Tz>()
+                .map(Some)
+                .map_err(|_| ConvertError::Config(format!("Unknown timezone {}", tz))),
+        }
+    }
+
+    /// Parse a timestamp with the time format in the layout's timezone.
+    /// Formats with an offset like `%z` ignore the timezone.
+    pub fn parse_time(&self, time: &str) -> ConvertResult<DateTime<Utc>> {
+        if ["%z", "%:z", "%#z", "%+"].iter().any(|z| self.tformat.contains(z)) {
+            return DateTime::parse_from_str(time, &self.tformat)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| {
+                    ConvertError::Import(format!("Failed to parse date {}: {}", time, e))
+                });
+        }
+        let naive = NaiveDateTime::parse_from_str(time, &self.tformat)
+            .map_err(|e| ConvertError::Import(format!("Failed to parse date {}: {}", time, e)))?;
+        self.local_time(&naive)
+    }
+
+    /// Convert a time without offset from the layout's timezone.
+    pub fn local_time(&self, naive: &NaiveDateTime) -> ConvertResult<DateTime<Utc>> {
+        match self.timezone()? {
+            None => Ok(Utc.from_utc_datetime(naive)),
+            Some(tz) => tz.from_local_datetime(naive)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc))
+                .ok_or_else(|| {
+                    ConvertError::Import(format!("Date {} does not exist in {}", naive, tz))
+                }),
+        }
+    }
+
+    /// The type of some column, string if nothing is configured.
+    pub fn field_type(&self, column: &str) -> FieldType {
+        self.types.get(column).cloned().unwrap_or_default()
+    }
+
+    /// Rewrite a number with the separators of the layout
+    /// into the form expected by rust, e.g. `1.234,5` into `1234.5`.
+    pub fn normalize(&self, value: &str) -> String {
+        value
+            .trim()
+            .chars()
+            .filter(|&c| Some(c) != self.thousands_sep)
+            .map(|c| match c == self.decimal_sep {
+                true => '.',
+                false => c,
+            })
+            .collect()
+    }
+
+    /// Convert the raw value of some column into its typed field value.
+    /// Numbers are normalized with the separators of the layout first.
+    pub fn parse_value(&self, column: &str, value: &str) -> ConvertResult<FieldValue> {
+        let field = self.field_type(column);
+        let parsed = match field {
+            FieldType::Integer | FieldType::Float => field.parse(&self.normalize(value)),
+            _ => field.parse(value),
+        };
+        parsed.map_err(|_| {
+            ConvertError::Import(format!(
+                "Failed to parse {} of column {} as {}",
+                value,
+                column,
+                field.name()
+            ))
+        })
+    }
+}
+
+/// Settings of sqlite databases, the `[sqlite]` table of the layout.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SqliteLayout {
+    /// Query of the database, `:last` is bound
+    /// to the greatest time imported before.
+    pub query: Option<String>,
+}
+
+/// Settings of log files, the `[log]` table of the layout.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogLayout {
+    /// Pattern with named captures to match the lines.
+    pub pattern: Option<String>,
+    /// Named patterns which can be referenced by the pattern.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub patterns: BTreeMap<String, String>,
+    /// Reject the lines which don't match the pattern.
+    #[serde(skip_serializing_if = "::std::ops::Not::not")]
+    pub report_unmatched: bool,
+}
+
+/// Settings of line protocol files, the `[line]` table of the layout.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LineLayout {
+    /// New names of the measurements, `*` renames all.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub rename: BTreeMap<String, String>,
+    /// Tags added to every point.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub add_tags: BTreeMap<String, String>,
+}
+
+/// Settings of fixed width files, the `[fixed]` table of the layout.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FixedLayout {
+    /// Columns of the lines.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<Column>,
+}
+
+/// Settings of workbooks, the `[sheet]` table of the layout.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SheetLayout {
+    /// Name of the sheet, `*` reads all sheets, the first if not set.
+    pub name: Option<String>,
+    /// Add the name of the sheet as tag with this name.
+    pub tag: Option<String>,
+}
+
+/// Settings of prometheus metrics, the `[prometheus]` table of the layout.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrometheusLayout {
+    /// Write the metrics as fields of the series
+    /// instead of measurements of their own.
+    #[serde(skip_serializing_if = "::std::ops::Not::not")]
+    pub metric_fields: bool,
+    /// Time of the samples without timestamp,
+    /// the modification time of the file if not set.
+    pub default_time: Option<String>,
+}
+
+/// Whether some settings are left at their defaults
+/// and can be left out of the layout file.
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// Return the decimal and thousands separator of some locale
+/// like `de`, `de_CH` or `en_US.UTF-8`.
+pub fn separators(locale: &str) -> ConvertResult<(char, Option<char>)> {
+    let mut parts = locale.split(['_', '-', '.']);
+    let language = parts.next().unwrap_or_default().to_lowercase();
+    let region = parts.next().unwrap_or_default().to_uppercase();
+    match (language.as_str(), region.as_str()) {
+        ("de", "CH") | ("fr", "CH") | ("it", "CH") => Ok(('.', Some('\''))),
+        ("c", _) | ("posix", _) | ("en", _) | ("ja", _) | ("ko", _) | ("zh", _) => {
+            Ok(('.', Some(',')))
+        }
+        ("de", _) | ("nl", _) | ("da", _) | ("it", _) | ("es", _) | ("pt", _) | ("tr", _)
+        | ("el", _) | ("id", _) | ("ro", _) | ("hr", _) | ("sl", _) => Ok((',', Some('.'))),
+        ("fr", _) | ("ru", _) | ("pl", _) | ("cs", _) | ("sk", _) | ("fi", _) | ("sv", _)
+        | ("nb", _) | ("no", _) | ("uk", _) | ("hu", _) | ("bg", _) => Ok((',', Some(' '))),
+        _ => Err(ConvertError::Config(format!("Unknown locale {}", locale))),
+    }
+}
+
+/// The formats of the input files.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Json,
+    Sqlite,
+    Log,
+    Line,
+    Fixed,
+    Sheet,
+    Mscons,
+    Parquet,
+    Arrow,
+    Prometheus,
+}
+
+impl Format {
+    /// Parse a format from its name as used in the layout file.
+    pub fn from_name(name: &str) -> ConvertResult<Format> {
+        match name.trim() {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "sqlite" => Ok(Format::Sqlite),
+            "log" => Ok(Format::Log),
+            "line" => Ok(Format::Line),
+            "fixed" => Ok(Format::Fixed),
+            "sheet" => Ok(Format::Sheet),
+            "mscons" => Ok(Format::Mscons),
+            "parquet" => Ok(Format::Parquet),
+            "arrow" => Ok(Format::Arrow),
+            "prometheus" => Ok(Format::Prometheus),
+            n => Err(ConvertError::Config(format!("Unknown format {}", n))),
+        }
+    }
+
+    /// Guess the format by the extension of some file
+    /// ignoring the extension of its compression.
+    pub fn from_extension(file: &str) -> Option<Format> {
+        let name = file.to_lowercase();
+        let name = [".gz", ".gzip", ".bz2", ".xz", ".zst", ".zstd"]
+            .iter()
+            .find_map(|c| name.strip_suffix(c))
+            .unwrap_or(&name);
+        match Path::new(name).extension().and_then(|e| e.to_str()) {
+            Some("csv") | Some("tsv") | Some("txt") => Some(Format::Csv),
+            Some("json") | Some("ndjson") | Some("jsonl") => Some(Format::Json),
+            Some("sqlite") | Some("sqlite3") | Some("db") => Some(Format::Sqlite),
+            Some("log") => Some(Format::Log),
+            Some("lp") => Some(Format::Line),
+            Some("xlsx") | Some("xlsm") | Some("xls") | Some("ods") => Some(Format::Sheet),
+            Some("edi") | Some("mscons") => Some(Format::Mscons),
+            Some("parquet") | Some("pq") => Some(Format::Parquet),
+            Some("arrow") | Some("arrows") | Some("feather") | Some("ipc") => Some(Format::Arrow),
+            Some("prom") | Some("metrics") => Some(Format::Prometheus),
+            _ => None,
+        }
+    }
+}
+
+/// The influxdb field types a value can be converted into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    #[default]
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+impl FieldType {
+    /// Convert a raw value into a typed field value.
+    pub fn parse(&self, value: &str) -> ConvertResult<FieldValue> {
+        let v = value.trim();
+        let err = |t| ConvertError::Import(format!("Failed to parse {} as {}", value, t));
+        match *self {
+            FieldType::String => Ok(FieldValue::String(value.into())),
+            FieldType::Integer => v.parse()
+                .or_else(|_| match v.parse::<f64>() {
+                    Ok(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Ok(f as i64),
+                    _ => Err(err("integer")),
+                })
+                .map(FieldValue::Integer),
+            FieldType::Float => v.parse().map(FieldValue::Float).map_err(|_| err("float")),
+            FieldType::Boolean => match v.to_lowercase().as_str() {
+                "true" | "t" | "yes" | "1" => Ok(FieldValue::Boolean(true)),
+                "false" | "f" | "no" | "0" => Ok(FieldValue::Boolean(false)),
+                _ => Err(err("boolean")),
+            },
+        }
+    }
+
+    /// The name of the type as used in the layout file.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FieldType::String => "string",
+            FieldType::Integer => "integer",
+            FieldType::Float => "float",
+            FieldType::Boolean => "boolean",
+        }
+    }
+
+    /// Parse a type from its name as used in the layout file.
+    pub fn from_name(name: &str) -> ConvertResult<FieldType> {
+        match name.trim() {
+            "string" => Ok(FieldType::String),
+            "integer" => Ok(FieldType::Integer),
+            "float" => Ok(FieldType::Float),
+            "boolean" => Ok(FieldType::Boolean),
+            n => Err(ConvertError::Config(format!("Unknown field type {}", n))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mapper::test::temp;
+
+    #[test]
+    fn test_load_and_save() {
+        let layout = Layout::load("assets/test.toml").unwrap();
+        assert_eq!(layout.measure, "Profilwert kWh");
+        assert_eq!(layout.delimiter, ';');
+        assert_eq!(layout.skip_rows, 10);
+        assert_eq!(layout.field_type("Profilwert kWh"), FieldType::Float);
+        assert_eq!(layout.field_type("Status"), FieldType::String);
+        assert_eq!(layout.preamble.len(), 3);
+
+        let path = temp("layout.toml");
+        layout.save(&path).unwrap();
+        assert_eq!(Layout::load(&path).unwrap(), layout);
+    }
+
+    #[test]
+    fn test_format_tables() {
+        let text = "format = \"csv\"\n[log]\npattern = \"(\"\n[sheet]\nname = \"*\"\n";
+        let mut layout: Layout = toml::from_str(text).unwrap();
+        assert_eq!(layout.sheet.name.as_deref(), Some("*"));
+        // the broken pattern is only checked for log files
+        assert!(layout.check().is_ok());
+        layout.format = Some(Format::Log);
+        assert!(layout.check().is_err());
+        layout.format = None;
+        assert!(layout.check().is_err());
+
+        assert!(toml::from_str::<Layout>("pattern = \"(\"").is_err());
+        assert!(toml::from_str::<Layout>("[sqlite]\npattern = \"(\"").is_err());
+        let saved = toml::to_string(&Layout::default()).unwrap();
+        assert!(!saved.contains("[sqlite]"));
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let mut layout = Layout {
+            tformat: "%d.%m.%Y %H:%M".into(),
+            ..Layout::default()
+        };
+        let utc = layout.parse_time("01.07.2016 12:00").unwrap();
+        assert_eq!(utc.to_rfc3339(), "2016-07-01T12:00:00+00:00");
+
+        layout.timezone = Some("Europe/Berlin".into());
+        let cest = layout.parse_time("01.07.2016 12:00").unwrap();
+        assert_eq!(cest.to_rfc3339(), "2016-07-01T10:00:00+00:00");
+        assert!(layout.parse_time("2016-07-01 12:00").is_err());
+
+        layout.tformat = "%d/%b/%Y:%H:%M:%S %z".into();
+        let offset = layout.parse_time("10/Oct/2016:13:55:36 +0200").unwrap();
+        assert_eq!(offset.to_rfc3339(), "2016-10-10T11:55:36+00:00");
+
+        layout.tformat = "%d.%m.%Y %H:%M".into();
+        layout.timezone = Some("Mars/Olympus".into());
+        assert!(layout.parse_time("01.07.2016 12:00").is_err());
+    }
+
+    #[test]
+    fn test_field_types() {
+        assert_eq!(
+            FieldType::Float.parse("103.5").unwrap(),
+            FieldValue::Float(103.5)
+        );
+        assert_eq!(
+            FieldType::Integer.parse(" 220").unwrap(),
+            FieldValue::Integer(220)
+        );
+        assert_eq!(
+            FieldType::Boolean.parse("True").unwrap(),
+            FieldValue::Boolean(true)
+        );
+        assert!(FieldType::Integer.parse("103.5").is_err());
+        assert_eq!(
+            FieldType::Integer.parse("1.53E+07").unwrap(),
+            FieldValue::Integer(15_300_000)
+        );
+        assert_eq!(FieldType::from_name("float").unwrap(), FieldType::Float);
+        assert_eq!(Format::from_extension("a/2016.ndjson.gz"), Some(Format::Json));
+        assert_eq!(Format::from_extension("2016.CSV"), Some(Format::Csv));
+        assert_eq!(Format::from_extension("Zähler.xlsx"), Some(Format::Sheet));
+        assert_eq!(Format::from_extension("MSCONS_1.edi"), Some(Format::Mscons));
+        assert_eq!(Format::from_extension("sensors.feather"), Some(Format::Arrow));
+        assert_eq!(Format::from_extension("node.prom.gz"), Some(Format::Prometheus));
+        assert_eq!(Layout::default().format("-"), Format::Csv);
+        assert!(FieldType::from_name("double").is_err());
+    }
+
+    #[test]
+    fn test_locale_numbers() {
+        let mut layout = Layout {
+            types: vec![("kWh".to_string(), FieldType::Float)].into_iter().collect(),
+            ..Layout::default()
+        };
+        layout.set_locale("de_DE.UTF-8").unwrap();
+        assert_eq!(layout.parse_value("kWh", "1.234,5").unwrap(), FieldValue::Float(1234.5));
+        assert_eq!(layout.parse_value("kWh", "1,53E+07").unwrap(), FieldValue::Float(1.53e7));
+        assert_eq!(
+            layout.parse_value("meter", "1,53E+07").unwrap(),
+            FieldValue::String("1,53E+07".into())
+        );
+        let err = layout.parse_value("kWh", "n/a").unwrap_err();
+        assert_eq!(format!("{}", err), "Failed to parse n/a of column kWh as float.");
+
+        layout.set_locale("de_CH").unwrap();
+        assert_eq!(layout.parse_value("kWh", "1'234.5").unwrap(), FieldValue::Float(1234.5));
+        assert!(layout.set_locale("xx").is_err());
+
+        layout.thousands_sep = Some('.');
+        assert!(layout.check().is_err());
+    }
+}
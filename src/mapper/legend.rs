@@ -0,0 +1,246 @@
+//! Look up the values of some tag in a legend, e.g. the
+//! status codes of load profiles. The description is added
+//! as tag, replacing the code if the tag is the column itself.
+//! A legend can also select the points to import and write
+//! the measure of some values to another field.
+//!
+//! ```toml
+//! tags = ["Status"]
+//!
+//! [[legend]]
+//! column = "Status"
+//! tag = "quality"
+//! file = "status.csv"
+//! only = ["220", "67"]
+//!
+//! [legend.values]
+//! 220 = "valid"
+//! 67 = "substituted"
+//!
+//! [legend.fields]
+//! 67 = "substituted kWh"
+//! ```
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use client::Message;
+use super::{source, Layout, Sink};
+use error::{ConvertError, ConvertResult};
+
+/// A lookup table for the values of a single tag.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Legend {
+    /// The tag whose values are looked up.
+    pub column: String,
+    /// Add the description as tag with this name.
+    pub tag: Option<String>,
+    /// Csv file with a header row and a value and
+    /// its description in each further row.
+    pub file: Option<String>,
+    /// Descriptions by value, these win over the ones of the file.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, String>,
+    /// Only import the points with these values, all if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<String>,
+    /// Write the measure of the points with these values
+    /// to the field with the given name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Legend {
+    /// Return an error if the legend can't be used with the tags.
+    pub fn check(&self, tags: &[String]) -> ConvertResult<()> {
+        if !tags.contains(&self.column) {
+            return Err(ConvertError::Config(format!(
+                "The legend column {} is not a tag",
+                self.column
+            )));
+        }
+        if self.tag.is_none() && self.only.is_empty() && self.fields.is_empty() {
+            return Err(ConvertError::Config(format!(
+                "The legend of {} needs a tag, only or fields",
+                self.column
+            )));
+        }
+        Ok(())
+    }
+
+    /// The descriptions of the file and the inline ones.
+    fn read(&self) -> ConvertResult<BTreeMap<String, String>> {
+        let mut table = BTreeMap::new();
+        if let Some(ref file) = self.file {
+            // the first row names the columns
+            for line in source::open(file, None)?.lines().skip(1) {
+                let line = line?;
+                let cell = |c: &str| c.trim().trim_matches('"').to_owned();
+                if let Some((value, description)) = line.split_once([';', ',', '\t']) {
+                    table.insert(cell(value), cell(description));
+                }
+            }
+        }
+        table.extend(self.values.clone());
+        Ok(table)
+    }
+}
+
+/// The legends of some layout with their descriptions read.
+#[derive(Debug, Default)]
+pub struct Legends(Vec<(Legend, BTreeMap<String, String>)>);
+
+impl Legends {
+    /// Read the legend files of the layout.
+    pub fn new(layout: &Layout) -> ConvertResult<Legends> {
+        layout
+            .legend
+            .iter()
+            .map(|l| l.read().map(|t| (l.clone(), t)))
+            .collect::<ConvertResult<_>>()
+            .map(Legends)
+    }
+
+    /// Describe the tags of some message, none if it is not imported.
+    pub fn apply(&self, msg: Message) -> Option<Message> {
+        if self.0.is_empty() {
+            return Some(msg);
+        }
+        let mut tags = msg.tags().to_vec();
+        let mut field = msg.value().clone();
+        for (legend, table) in &self.0 {
+            let value = match tags.iter().find(|t| t.0 == legend.column) {
+                Some(t) => t.1.clone(),
+                None => String::new(),
+            };
+            if !legend.only.is_empty() && !legend.only.contains(&value) {
+                return None;
+            }
+            if let Some(f) = legend.fields.get(&value) {
+                field.0 = f.clone();
+            }
+            if let (Some(tag), Some(description)) = (&legend.tag, table.get(&value)) {
+                tags.retain(|t| &t.0 != tag);
+                tags.push((tag.clone(), description.clone()));
+            }
+        }
+        Some(Message::new(msg.series().to_owned(), msg.time(), field, tags))
+    }
+
+    /// Pass the described points on to the sink.
+    pub fn sink<'a>(&'a self, inner: &'a dyn Sink) -> Described<'a> {
+        Described {
+            legends: self,
+            inner,
+        }
+    }
+}
+
+/// A sink which describes the points before passing them on.
+pub struct Described<'a> {
+    legends: &'a Legends,
+    inner: &'a dyn Sink,
+}
+
+impl<'a> Sink for Described<'a> {
+    fn send(&self, msg: Message) -> ConvertResult<()> {
+        match self.legends.apply(msg) {
+            Some(m) => self.inner.send(m),
+            None => Ok(()),
+        }
+    }
+
+    fn reject(&self, source: &str, row: usize, err: ConvertError) {
+        self.inner.reject(source, row, err)
+    }
+
+    fn progress(&self, source: &str, row: usize, done: bool) {
+        self.inner.progress(source, row, done)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+    use client::FieldValue;
+    use mapper::test::temp;
+    use std::fs;
+
+    fn msg(status: &str) -> Message {
+        Message::new(
+            "meter".into(),
+            Utc::now(),
+            ("kWh".into(), FieldValue::Float(1.5)),
+            vec![("Status".into(), status.into())],
+        )
+    }
+
+    #[test]
+    fn test_apply() {
+        let path = temp("status.csv");
+        fs::write(&path, "Status;Bedeutung\n220;Wahrer Wert\n67;Ersatzwert\n").unwrap();
+        let mut layout = Layout {
+            tags: vec!["Status".into()],
+            ..Layout::default()
+        };
+        let mut legend = Legend {
+            column: "Status".into(),
+            tag: Some("quality".into()),
+            file: Some(path.to_str().unwrap().into()),
+            only: vec!["220".into(), "67".into()],
+            ..Legend::default()
+        };
+        legend.values.insert("220".into(), "valid".into());
+        legend.fields.insert("67".into(), "substituted kWh".into());
+        assert!(legend.check(&layout.tags).is_ok());
+        layout.legend.push(legend.clone());
+
+        let legends = Legends::new(&layout).unwrap();
+        let valid = legends.apply(msg("220")).unwrap();
+        assert_eq!(valid.value().0, "kWh");
+        assert_eq!(valid.tags()[1], ("quality".to_string(), "valid".to_string()));
+        let substituted = legends.apply(msg("67")).unwrap();
+        assert_eq!(substituted.value().0, "substituted kWh");
+        assert_eq!(substituted.tags()[1], ("quality".to_string(), "Ersatzwert".to_string()));
+        assert!(legends.apply(msg("20")).is_none());
+
+        // the description replaces the code of the column
+        legend.tag = Some("Status".into());
+        legend.only.clear();
+        layout.legend = vec![legend.clone()];
+        let replaced = Legends::new(&layout).unwrap().apply(msg("220")).unwrap();
+        assert_eq!(replaced.tags(), &[("Status".to_string(), "valid".to_string())]);
+
+        assert!(legend.check(&[]).is_err());
+        let nothing = Legend {
+            column: "Status".into(),
+            ..Legend::default()
+        };
+        assert!(nothing.check(&layout.tags).is_err());
+    }
+
+    #[test]
+    fn test_layout_dir() {
+        let dir = temp("legend");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("status.csv"), "Status;Bedeutung\n220;Wahrer Wert\n").unwrap();
+        fs::write(
+            dir.join("layout.toml"),
+            "tags = [\"Status\"]\n[[legend]]\ncolumn = \"Status\"\ntag = \"Status\"\nfile = \"status.csv\"\n",
+        ).unwrap();
+
+        let layout = Layout::load(dir.join("layout.toml")).unwrap();
+        let legends = Legends::new(&layout).unwrap();
+        assert_eq!(legends.0[0].1.len(), 1);
+        let described = legends.apply(msg("220")).unwrap();
+        assert_eq!(described.tags(), &[("Status".to_string(), "Wahrer Wert".to_string())]);
+
+        // the resolved file is kept when the layout is saved elsewhere
+        layout.save(dir.join("saved.toml")).unwrap();
+        let saved: Layout = ::toml::from_str(&fs::read_to_string(dir.join("saved.toml")).unwrap())
+            .unwrap();
+        assert_eq!(saved.legend, layout.legend);
+        assert!(Legends::new(&saved).is_ok());
+    }
+}
@@ -0,0 +1,301 @@
+//! Import files in the influxdb line protocol, e.g. backups
+//! written by `influx_inspect export`.
+//!
+//! Every line is validated and each field becomes its own
+//! message. Measurements can be renamed and tags added on the
+//! way through. Timestamps are read as nanoseconds, lines
+//! without timestamp get the current time.
+//!
+//! ```toml
+//! format = "line"
+//!
+//! [line.rename]
+//! cpu = "cpu_old"
+//!
+//! [line.add_tags]
+//! restored = "2016-07-01"
+//! ```
+use std::io::BufRead;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use chrono::{DateTime, Utc};
+use client::{FieldValue, Message};
+use super::{Driver, Layout, Options, Sink};
+use super::source;
+use error::{ConvertError, ConvertResult};
+
+/// A single line of line protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    pub tags: Vec<(String, String)>,
+    pub fields: Vec<(String, FieldValue)>,
+    /// Nanoseconds since the epoch.
+    pub time: Option<i64>,
+}
+
+/// An import error of some invalid line.
+fn invalid(msg: String) -> ConvertError {
+    ConvertError::Import(msg)
+}
+
+/// Read up to one of the unescaped stop characters and return
+/// the text and the stop found, none at the end of the line.
+fn token(chars: &mut Peekable<Chars>, stops: &[char]) -> (String, Option<char>) {
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.peek() {
+                Some(&n) if matches!(n, '\\' | '=' | ',' | ' ') => {
+                    text.push(n);
+                    chars.next();
+                }
+                _ => text.push(c),
+            },
+            c if stops.contains(&c) => return (text, Some(c)),
+            c => text.push(c),
+        }
+    }
+    (text, None)
+}
+
+/// Read a quoted string value after its opening quote.
+fn quoted(chars: &mut Peekable<Chars>) -> ConvertResult<String> {
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'"') || chars.peek() == Some(&'\\') => {
+                text.push(chars.next().unwrap_or(c));
+            }
+            '"' => return Ok(text),
+            c => text.push(c),
+        }
+    }
+    Err(invalid("Unterminated string value".into()))
+}
+
+/// Parse a field value like `1.5`, `2i`, `3u`, `t` or a string.
+fn field(raw: &str) -> ConvertResult<FieldValue> {
+    let err = || invalid(format!("Invalid field value {}", raw));
+    if let Some(i) = raw.strip_suffix('i').or_else(|| raw.strip_suffix('u')) {
+        return i.parse().map(FieldValue::Integer).map_err(|_| err());
+    }
+    match raw {
+        "t" | "T" | "true" | "True" | "TRUE" => Ok(FieldValue::Boolean(true)),
+        "f" | "F" | "false" | "False" | "FALSE" => Ok(FieldValue::Boolean(false)),
+        r => r.parse().map(FieldValue::Float).map_err(|_| err()),
+    }
+}
+
+/// Parse and validate a single line.
+pub fn parse(line: &str) -> ConvertResult<Point> {
+    let mut chars = line.chars().peekable();
+    let (measurement, mut stop) = token(&mut chars, &[',', ' ']);
+    if measurement.is_empty() {
+        return Err(invalid("Missing measurement".into()));
+    }
+
+    let mut tags = vec![];
+    while stop == Some(',') {
+        let (key, eq) = token(&mut chars, &['=']);
+        let (value, s) = token(&mut chars, &[',', ' ']);
+        if key.is_empty() || eq.is_none() || value.is_empty() {
+            return Err(invalid(format!("Invalid tag {}={}", key, value)));
+        }
+        tags.push((key, value));
+        stop = s;
+    }
+    if stop.is_none() {
+        return Err(invalid("Missing fields".into()));
+    }
+
+    let mut fields = vec![];
+    loop {
+        let (key, eq) = token(&mut chars, &['=']);
+        if key.is_empty() || eq.is_none() {
+            return Err(invalid(format!("Invalid field {}", key)));
+        }
+        let (value, s) = match chars.peek() {
+            Some(&'"') => {
+                chars.next();
+                let value = FieldValue::String(quoted(&mut chars)?);
+                match chars.next() {
+                    None => (value, None),
+                    Some(c) if c == ',' || c == ' ' => (value, Some(c)),
+                    Some(c) => return Err(invalid(format!("Unexpected {} after string", c))),
+                }
+            }
+            _ => {
+                let (raw, s) = token(&mut chars, &[',', ' ']);
+                (field(&raw)?, s)
+            }
+        };
+        fields.push((key, value));
+        if s != Some(',') {
+            break;
+        }
+    }
+
+    let rest: String = chars.collect();
+    let time = match rest.trim() {
+        "" => None,
+        t => Some(t.parse().map_err(|_| invalid(format!("Invalid timestamp {}", t)))?),
+    };
+    Ok(Point {
+        measurement,
+        tags,
+        fields,
+        time,
+    })
+}
+
+/// A convenient struct to import line protocol files.
+#[derive(Debug, Default)]
+pub struct LineProtocol {
+    options: Options,
+}
+
+impl LineProtocol {
+    pub fn new(options: Options) -> LineProtocol {
+        LineProtocol { options }
+    }
+
+    /// Convert a point into one message for each field
+    /// renamed and tagged by the layout.
+    fn convert(&self, layout: &Layout, point: Point) -> Vec<Message> {
+        let series = layout
+            .line
+            .rename
+            .get(&point.measurement)
+            .or_else(|| layout.line.rename.get("*"))
+            .cloned()
+            .unwrap_or(point.measurement);
+        let time = match point.time {
+            Some(ns) => DateTime::from_timestamp_nanos(ns),
+            None => Utc::now(),
+        };
+        let mut tags = point.tags;
+        for (k, v) in &layout.line.add_tags {
+            tags.retain(|t| &t.0 != k);
+            tags.push((k.clone(), v.clone()));
+        }
+
+        point
+            .fields
+            .into_iter()
+            .map(|f| Message::new(series.clone(), time, f, tags.clone()))
+            .collect()
+    }
+
+    /// Read a single file and pass its points to the sink.
+    /// The file `-` is read from stdin.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let reader = source::open(file, layout.encoding.as_deref())?;
+        self.import_reader(file, reader, layout, sink)
+    }
+
+    /// Validate the lines of some reader and pass the points to the sink.
+    /// Empty lines and comments starting with `#` are skipped.
+    pub fn import_reader<R: BufRead>(
+        &self,
+        source: &str,
+        reader: R,
+        layout: &Layout,
+        sink: &dyn Sink,
+    ) -> ConvertResult<()> {
+        let mut driver = Driver::new(&self.options, source, sink);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let read = driver.row(i + 1, || {
+                let trimmed = line.trim();
+                if trimmed.is_empty() || trimmed.starts_with('#') {
+                    return vec![];
+                }
+                match parse(trimmed) {
+                    Ok(point) => self.convert(layout, point).into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                }
+            });
+            if !read {
+                break;
+            }
+        }
+        driver.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mapper::test::Collect;
+
+    #[test]
+    fn test_parse() {
+        let p = parse(r#"my\ cpu,host=a\,b,region=eu load=0.5,n=3i,ok=t,msg="say \"hi\", now" 1465839830100400200"#)
+            .unwrap();
+        assert_eq!(p.measurement, "my cpu");
+        assert_eq!(p.tags, vec![("host".into(), "a,b".into()), ("region".into(), "eu".into())]);
+        assert_eq!(
+            p.fields,
+            vec![
+                ("load".into(), FieldValue::Float(0.5)),
+                ("n".into(), FieldValue::Integer(3)),
+                ("ok".into(), FieldValue::Boolean(true)),
+                ("msg".into(), FieldValue::String("say \"hi\", now".into())),
+            ]
+        );
+        assert_eq!(p.time, Some(1465839830100400200));
+        assert_eq!(parse("cpu value=1").unwrap().time, None);
+
+        assert!(parse("cpu").is_err());
+        assert!(parse("cpu,host value=1").is_err());
+        assert!(parse("cpu value=1x").is_err());
+        assert!(parse(r#"cpu value="open"#).is_err());
+        assert!(parse("cpu value=1 soon").is_err());
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let data = "# DML\ncpu,host=a load=0.5,n=3i 1465839830000000000\ncpu load\nmem free=1 0\n";
+        let mut layout = Layout::default();
+        layout.line.rename.insert("cpu".into(), "cpu_old".into());
+        layout.line.add_tags.insert("host".into(), "restored".into());
+
+        let sink = Collect::default();
+        LineProtocol::default()
+            .import_reader("backup.lp", data.as_bytes(), &layout, &sink)
+            .unwrap();
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0].series(), "cpu_old");
+        assert_eq!(sent[0].time().timestamp(), 1465839830);
+        assert_eq!(sent[1].value(), &("n".to_string(), FieldValue::Integer(3)));
+        assert_eq!(sent[2].series(), "mem");
+        assert_eq!(sent[2].tags(), &[("host".to_string(), "restored".to_string())]);
+        assert_eq!(*sink.rejected.borrow(), vec![3]);
+
+        // the limit counts the lines after the resumed ones
+        let options = Options {
+            limit: Some(1),
+            resume: vec![("backup.lp".to_string(), 3)].into_iter().collect(),
+        };
+        let sink = Collect::default();
+        LineProtocol::new(options)
+            .import_reader("backup.lp", data.as_bytes(), &layout, &sink)
+            .unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(sink.sent.borrow()[0].series(), "mem");
+
+        // timestamps keep their nanoseconds
+        let data = "cpu load=0.5 1465839830100400200\n";
+        let sink = Collect::default();
+        LineProtocol::default()
+            .import_reader("backup.lp", data.as_bytes(), &layout, &sink)
+            .unwrap();
+        let sent = sink.sent.borrow();
+        assert_eq!(sent[0].time().timestamp_nanos_opt(), Some(1465839830100400200));
+        assert!(sent[0].line().ends_with(" 1465839830100400200"));
+    }
+}
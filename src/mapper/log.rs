@@ -0,0 +1,301 @@
+//! Import log files line by line with a regular expression.
+//!
+//! The named captures of the pattern are mapped like the columns
+//! of a csv file onto the time, measure and tags of the layout.
+//! Patterns can use grok-style references `%{NAME}` or
+//! `%{NAME:capture}` to the patterns below or to the `patterns`
+//! of the `[log]` table, e.g. `%{NGINX}` for the nginx combined format.
+//!
+//! ```toml
+//! format = "log"
+//! measure = "body_bytes_sent"
+//! time = "time_local"
+//! tformat = "%d/%b/%Y:%H:%M:%S %z"
+//! tags = ["status", "method"]
+//!
+//! [log]
+//! pattern = "%{NGINX}"
+//! ```
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use chrono::{Datelike, Utc};
+use regex::{Captures, Regex};
+use client::Message;
+use super::{Driver, Layout, Options, Sink};
+use super::source;
+use error::{ConvertError, ConvertResult};
+
+/// Patterns which can be referenced by name.
+const PATTERNS: &[(&str, &str)] = &[
+    ("INT", r"[+-]?\d+"),
+    ("NUMBER", r"[+-]?(?:\d+(?:\.\d*)?|\.\d+)"),
+    ("WORD", r"\w+"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("HOSTNAME", r"[\w.:-]+"),
+    ("HTTPDATE", r"\d{2}/\w{3}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4}"),
+    ("SYSLOGTIMESTAMP", r"\w{3} +\d{1,2} \d{2}:\d{2}:\d{2}"),
+    (
+        "TIMESTAMP_ISO8601",
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+    ),
+    (
+        "NGINX",
+        concat!(
+            r"%{NOTSPACE:remote_addr} - %{NOTSPACE:remote_user} \[%{HTTPDATE:time_local}\] ",
+            r#""%{WORD:method} %{NOTSPACE:request} %{DATA:protocol}" %{INT:status} "#,
+            r#"%{NOTSPACE:body_bytes_sent} "%{DATA:http_referer}" "%{DATA:http_user_agent}""#,
+            r"(?: %{NUMBER:request_time})?"
+        ),
+    ),
+    (
+        "SYSLOG",
+        r"%{SYSLOGTIMESTAMP:timestamp} %{HOSTNAME:host} %{DATA:program}(?:\[%{INT:pid}\])?: %{GREEDYDATA:message}",
+    ),
+];
+
+/// How deep patterns may reference other patterns.
+const DEPTH: usize = 10;
+
+/// Replace the pattern references of some pattern by their expressions.
+fn expand(pattern: &str, custom: &HashMap<&str, &str>, depth: usize) -> ConvertResult<String> {
+    if depth > DEPTH {
+        return Err(ConvertError::Config(format!(
+            "Pattern {} references too deep",
+            pattern
+        )));
+    }
+    let reference = Regex::new(r"%\{(\w+)(?::(\w+))?\}").expect("valid reference pattern");
+    let mut result = String::new();
+    let mut end = 0;
+    for c in reference.captures_iter(pattern) {
+        let all = c.get(0).expect("whole match");
+        let name = &c[1];
+        let inner = custom
+            .get(name)
+            .cloned()
+            .or_else(|| PATTERNS.iter().find(|p| p.0 == name).map(|p| p.1))
+            .ok_or_else(|| ConvertError::Config(format!("Unknown pattern {}", name)))?;
+        let inner = expand(inner, custom, depth + 1)?;
+        result.push_str(&pattern[end..all.start()]);
+        match c.get(2) {
+            Some(capture) => result.push_str(&format!("(?P<{}>{})", capture.as_str(), inner)),
+            None => result.push_str(&format!("(?:{})", inner)),
+        }
+        end = all.end();
+    }
+    result.push_str(&pattern[end..]);
+    Ok(result)
+}
+
+/// Compile the pattern of the layout with its references expanded.
+pub fn compile(layout: &Layout) -> ConvertResult<Regex> {
+    let pattern = layout
+        .log
+        .pattern
+        .as_ref()
+        .ok_or_else(|| ConvertError::Config("Log files need a pattern".into()))?;
+    let custom = layout
+        .log
+        .patterns
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    let expanded = expand(pattern, &custom, 0)?;
+    Regex::new(&expanded)
+        .map_err(|e| ConvertError::Config(format!("Invalid pattern {}: {}", pattern, e)))
+}
+
+/// Parse the time of some line. Times without a year
+/// like the ones of syslog are taken from this year.
+fn time(layout: &Layout, value: &str) -> ConvertResult<::chrono::DateTime<Utc>> {
+    let years = ["%Y", "%y", "%G", "%s", "%+", "%c", "%D", "%F"];
+    if years.iter().any(|y| layout.tformat.contains(y)) {
+        return layout.parse_time(value);
+    }
+    let with_year = Layout {
+        tformat: format!("%Y {}", layout.tformat),
+        ..layout.clone()
+    };
+    with_year.parse_time(&format!("{} {}", Utc::now().year(), value))
+}
+
+/// A convenient struct to import log files.
+///
+/// Each line is matched with the pattern of the layout,
+/// lines which don't match are counted and skipped.
+#[derive(Debug, Default)]
+pub struct Log {
+    options: Options,
+}
+
+impl Log {
+    pub fn new(options: Options) -> Log {
+        Log { options }
+    }
+
+    /// Convert the captures of a single line.
+    fn convert(&self, layout: &Layout, captures: &Captures) -> ConvertResult<Message> {
+        let get = |name: &str| {
+            captures
+                .name(name)
+                .map(|m| m.as_str())
+                .ok_or_else(|| ConvertError::NotFound(name.into()))
+        };
+        let value = layout.parse_value(&layout.measure, get(&layout.measure)?)?;
+        let timestamp = time(layout, get(&layout.time)?)?;
+        // captures of optional groups may be missing
+        let tags = layout
+            .tags
+            .iter()
+            .filter_map(|t| get(t).ok().map(|v| (t.clone(), v.to_owned())))
+            .collect();
+
+        Ok(Message::new(
+            layout.series.clone(),
+            timestamp,
+            (layout.measure.clone(), value),
+            tags,
+        ))
+    }
+
+    /// Read a single file and pass its lines to the sink.
+    /// The file `-` is read from stdin.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let reader = source::open(file, layout.encoding.as_deref())?;
+        self.import_reader(file, reader, layout, sink)
+    }
+
+    /// Match the lines of some reader and pass them to the sink.
+    pub fn import_reader<R: BufRead>(
+        &self,
+        source: &str,
+        reader: R,
+        layout: &Layout,
+        sink: &dyn Sink,
+    ) -> ConvertResult<()> {
+        let pattern = compile(layout)?;
+        let mut driver = Driver::new(&self.options, source, sink);
+        let mut unmatched = 0;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let read = driver.row(i + 1, || match pattern.captures(&line) {
+                Some(c) => Some(self.convert(layout, &c)),
+                None if line.trim().is_empty() => None,
+                None => {
+                    unmatched += 1;
+                    match layout.log.report_unmatched {
+                        true => {
+                            let err = format!("Line doesn't match: {}", line);
+                            Some(Err(ConvertError::Import(err)))
+                        }
+                        false => None,
+                    }
+                }
+            });
+            if !read {
+                break;
+            }
+        }
+
+        if unmatched > 0 {
+            info!(format!("{}: {} lines didn't match the pattern", source, unmatched));
+        }
+        driver.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use client::FieldValue;
+    use mapper::{FieldType, LogLayout};
+    use mapper::test::Collect;
+
+    #[test]
+    fn test_expand() {
+        let custom = vec![("LEVEL", "INFO|WARN|%{WORD}")].into_iter().collect();
+        assert_eq!(
+            expand("%{LEVEL:level} %{INT}", &custom, 0).unwrap(),
+            r"(?P<level>INFO|WARN|(?:\w+)) (?:[+-]?\d+)"
+        );
+        assert!(expand("%{MISSING}", &custom, 0).is_err());
+        let looped = vec![("A", "%{A}")].into_iter().collect();
+        assert!(expand("%{A}", &looped, 0).is_err());
+    }
+
+    #[test]
+    fn test_nginx() {
+        let data = concat!(
+            r#"127.0.0.1 - - [10/Oct/2016:13:55:36 +0200] "GET /index.html HTTP/1.1" 200 2326 "-" "curl/7.50""#,
+            "\n",
+            "garbage\n",
+            r#"10.0.0.2 - bob [10/Oct/2016:13:56:00 +0200] "POST /api HTTP/1.1" 500 12 "-" "-" 0.250"#,
+            "\n"
+        );
+        let mut layout = Layout {
+            measure: "body_bytes_sent".into(),
+            time: "time_local".into(),
+            tformat: "%d/%b/%Y:%H:%M:%S %z".into(),
+            tags: vec!["status".into(), "request_time".into()],
+            log: LogLayout {
+                pattern: Some("%{NGINX}".into()),
+                report_unmatched: true,
+                ..LogLayout::default()
+            },
+            ..Layout::default()
+        };
+        layout.types.insert("body_bytes_sent".into(), FieldType::Integer);
+
+        let sink = Collect::default();
+        Log::default()
+            .import_reader("access.log", data.as_bytes(), &layout, &sink)
+            .unwrap();
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].value().1, FieldValue::Integer(2326));
+        assert_eq!(sent[0].time().to_rfc3339(), "2016-10-10T11:55:36+00:00");
+        assert_eq!(sent[0].tags(), &[("status".to_string(), "200".to_string())]);
+        assert_eq!(sent[1].tags()[1], ("request_time".to_string(), "0.250".to_string()));
+        assert_eq!(*sink.rejected.borrow(), vec![2]);
+
+        // the limit counts the lines after the resumed ones
+        let options = Options {
+            limit: Some(1),
+            resume: vec![("access.log".to_string(), 2)].into_iter().collect(),
+        };
+        let sink = Collect::default();
+        Log::new(options)
+            .import_reader("access.log", data.as_bytes(), &layout, &sink)
+            .unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(sink.sent.borrow()[0].value().1, FieldValue::Integer(12));
+    }
+
+    #[test]
+    fn test_syslog() {
+        let data = "Oct 11 22:14:15 gateway sshd[4721]: Failed password for root\n";
+        let layout = Layout {
+            measure: "message".into(),
+            tformat: "%b %d %H:%M:%S".into(),
+            tags: vec!["host".into(), "program".into()],
+            log: LogLayout {
+                pattern: Some("%{SYSLOG}".into()),
+                ..LogLayout::default()
+            },
+            ..Layout::default()
+        };
+        let sink = Collect::default();
+        Log::default()
+            .import_reader("syslog", data.as_bytes(), &layout, &sink)
+            .unwrap();
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].time().year(), Utc::now().year());
+        assert_eq!(sent[0].tags()[1], ("program".to_string(), "sshd".to_string()));
+    }
+}
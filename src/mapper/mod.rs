@@ -4,40 +4,106 @@
 //! some data mapper.
 
 pub use error::{ConvertError, ConvertResult};
-use client::InfluxClient;
+use client::Message;
 
 pub use self::interactive::Interactive;
+pub use self::arrow::Arrow;
 pub use self::csv::Csv;
+pub use self::json::Json;
+pub use self::fixed::Column;
+pub use self::importer::{Driver, Importer, Options};
+pub use self::layout::{FieldType, FixedLayout, Format, Layout, LineLayout, LogLayout,
+                        PrometheusLayout, SheetLayout, SqliteLayout};
+pub use self::legend::{Legend, Legends};
+pub use self::line::LineProtocol;
+pub use self::log::Log;
+pub use self::mscons::Mscons;
+pub use self::prometheus::Prometheus;
+pub use self::rules::Rule;
+pub use self::sheet::Sheet;
+pub use self::sql::Sql;
+pub use self::infer::{infer, sample};
+pub use self::inputs::{Inputs, Order};
+pub use self::state::{hash, Checkpoints, State};
 
 mod interactive;
+mod arrow;
 mod csv;
+mod json;
+mod fixed;
+mod follow;
+mod importer;
+mod layout;
+mod legend;
+mod line;
+mod log;
+mod mscons;
+mod prometheus;
+mod rules;
+mod sheet;
+mod infer;
+mod inputs;
+mod preamble;
+mod source;
+mod sql;
+mod state;
 
-/// A layout describes the names for the database
-/// fields used by influx.
-/// See https://docs.rs/chrono/0.4.0/chrono/format/strftime/index.html
-/// for time formatting.
-#[derive(Debug, Clone)]
-pub struct Layout {
-    pub measure: String,
-    pub tags: Vec<String>,
-    pub time: String,
-    pub tformat: String,
-}
+/// A sink receives the messages produced by some mapper,
+/// e.g. the influx client.
+pub trait Sink {
+    /// Returns error if the message could not be passed on.
+    fn send(&self, msg: Message) -> ConvertResult<()>;
 
-impl Default for Layout {
-    fn default() -> Self {
-        Layout {
-            measure: String::from("data"),
-            tags: [].to_vec(),
-            time: String::from("timestamp"),
-            tformat: String::from("%F %H:%M:%S"),
-        }
+    /// Report some row of a source which could not be converted.
+    fn reject(&self, source: &str, row: usize, err: ConvertError) {
+        error!(format!("{}:{}: {}", source, row, err));
     }
+
+    /// Called once all rows of some source up to `row` were passed on,
+    /// `done` if the source has no more rows.
+    fn progress(&self, _source: &str, _row: usize, _done: bool) {}
 }
 
 /// A mapper applies the given `Layout` to some
 /// piece of data.
 pub trait Mapper {
     /// Returns error if the mapping or sending process failed.
-    fn import(&self, layout: &Layout, client: &InfluxClient) -> ConvertResult<()>;
+    fn import(&self, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()>;
+}
+
+#[cfg(test)]
+pub mod test {
+    //! Helpers shared by the tests of the mappers.
+    use std::cell::RefCell;
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+
+    use client::Message;
+    use super::Sink;
+    use error::{ConvertError, ConvertResult};
+
+    /// Collect the sent messages and the rows of the rejected ones.
+    #[derive(Default)]
+    pub struct Collect {
+        pub sent: RefCell<Vec<Message>>,
+        pub rejected: RefCell<Vec<usize>>,
+    }
+
+    impl Sink for Collect {
+        fn send(&self, msg: Message) -> ConvertResult<()> {
+            self.sent.borrow_mut().push(msg);
+            Ok(())
+        }
+
+        fn reject(&self, _: &str, row: usize, _: ConvertError) {
+            self.rejected.borrow_mut().push(row);
+        }
+    }
+
+    /// A path in the temp directory which is unique to the test
+    /// run, so concurrent runs don't write the same files.
+    pub fn temp(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("x-influx-test-{}-{}", process::id(), name))
+    }
 }
@@ -0,0 +1,465 @@
+//! Import load profiles of EDIFACT MSCONS messages as sent by
+//! german grid operators.
+//!
+//! Each quantity `QTY` becomes a point with the field named by the
+//! measure of the layout at the start of its interval, `time = "end"`
+//! uses the end instead. The tags of the layout are taken from the
+//! values below, the time format of the layout is not used.
+//!
+//! - `location`: market or metering location of `LOC+172`
+//! - `obis`: OBIS code of `PIA+5`, e.g. `1-1:1.29.0`
+//! - `qualifier`: qualifier of the quantity, e.g. `220` for true values
+//!   or `67` for substitute values
+//! - `status`: comma separated reasons of the `STS` segments
+//! - `unit`: unit of the quantity if given, e.g. `KWH`
+//! - `sender` and `receiver`: parties of `NAD+MS` and `NAD+MR`
+//! - `start` and `end`: the interval in RFC 3339
+//!
+//! ```toml
+//! format = "mscons"
+//! series = "meter"
+//! measure = "kWh"
+//! tags = ["location", "obis", "qualifier", "status"]
+//! ```
+use std::io::BufRead;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use client::{FieldValue, Message};
+use super::{Driver, Layout, Options, Sink};
+use super::source;
+use error::{ConvertError, ConvertResult};
+
+/// The separators of some interchange, given by `UNA` or the defaults.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Separators {
+    component: char,
+    element: char,
+    decimal: char,
+    release: char,
+    segment: char,
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Separators {
+            component: ':',
+            element: '+',
+            decimal: '.',
+            release: '?',
+            segment: '\'',
+        }
+    }
+}
+
+/// A segment with its data elements split into components.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment(Vec<Vec<String>>);
+
+impl Segment {
+    /// The tag of the segment, e.g. `QTY`.
+    pub fn tag(&self) -> &str {
+        self.get(0, 0)
+    }
+
+    /// Some component of some data element, empty if not given.
+    pub fn get(&self, element: usize, component: usize) -> &str {
+        self.0
+            .get(element)
+            .and_then(|e| e.get(component))
+            .map_or("", |c| c.as_str())
+    }
+}
+
+/// Split some text at the unreleased separator, the release
+/// characters are kept to split the parts again.
+fn split(text: &str, sep: char, release: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c == release => {
+                let last = parts.last_mut().expect("some part");
+                last.push(c);
+                last.extend(chars.next());
+            }
+            c if c == sep => parts.push(String::new()),
+            c => parts.last_mut().expect("some part").push(c),
+        }
+    }
+    parts
+}
+
+/// Remove the release characters of some component.
+fn unescape(text: &str, release: char) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c == release {
+            true => result.extend(chars.next()),
+            false => result.push(c),
+        }
+    }
+    result
+}
+
+/// Split an interchange into its segments.
+pub fn segments(text: &str) -> Vec<Segment> {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let mut sep = Separators::default();
+    let mut rest = text;
+    if text.starts_with("UNA") {
+        let una: Vec<char> = text.chars().skip(3).take(6).collect();
+        if una.len() == 6 {
+            sep = Separators {
+                component: una[0],
+                element: una[1],
+                decimal: una[2],
+                release: una[3],
+                segment: una[5],
+            };
+            rest = &text[text.char_indices().nth(9).map_or(text.len(), |(i, _)| i)..];
+        }
+    }
+
+    split(rest, sep.segment, sep.release)
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let elements = split(s, sep.element, sep.release)
+                .iter()
+                .map(|e| {
+                    split(e, sep.component, sep.release)
+                        .iter()
+                        .map(|c| unescape(c, sep.release))
+                        .collect()
+                })
+                .collect();
+            let mut segment = Segment(elements);
+            // numbers are kept with a decimal point
+            if segment.tag() == "QTY" && sep.decimal != '.' {
+                if let Some(q) = segment.0.get_mut(1).and_then(|e| e.get_mut(1)) {
+                    *q = q.replace(sep.decimal, ".");
+                }
+            }
+            segment
+        })
+        .collect()
+}
+
+/// Parse a date of `DTM` by its format code, dates without
+/// offset are read in the timezone of the layout.
+fn time(layout: &Layout, value: &str, format: &str) -> ConvertResult<DateTime<Utc>> {
+    let err = || ConvertError::Import(format!("Invalid date {} of format {}", value, format));
+    let (naive, offset) = match format {
+        "102" => (
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map_err(|_| err())?
+                .and_time(Default::default()),
+            None,
+        ),
+        "203" => (NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M").map_err(|_| err())?, None),
+        "204" => (NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S").map_err(|_| err())?, None),
+        "303" if value.len() > 12 && value.is_char_boundary(12) => {
+            let (t, z) = value.split_at(12);
+            let naive = NaiveDateTime::parse_from_str(t, "%Y%m%d%H%M").map_err(|_| err())?;
+            // the offset is given in hours, rarely with minutes
+            let z: i32 = z.parse().map_err(|_| err())?;
+            let seconds = match z.abs() > 99 {
+                true => z / 100 * 3600 + z % 100 * 60,
+                false => z * 3600,
+            };
+            (naive, Some(FixedOffset::east_opt(seconds).ok_or_else(err)?))
+        }
+        _ => return Err(err()),
+    };
+    let local = match (offset, layout.timezone()?) {
+        (Some(o), _) => o.from_local_datetime(&naive).single().map(|t| t.with_timezone(&Utc)),
+        (None, Some(tz)) => tz.from_local_datetime(&naive)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc)),
+        (None, None) => Some(Utc.from_utc_datetime(&naive)),
+    };
+    local.ok_or_else(err)
+}
+
+/// A single quantity with the context of its segment group.
+#[derive(Debug, Default)]
+struct Value {
+    row: usize,
+    quantity: String,
+    qualifier: String,
+    unit: String,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    status: Vec<String>,
+    error: Option<ConvertError>,
+}
+
+/// The parties and location the quantities belong to.
+#[derive(Debug, Default)]
+struct Context {
+    sender: String,
+    receiver: String,
+    location: String,
+    obis: String,
+}
+
+/// A convenient struct to import MSCONS files.
+#[derive(Debug, Default)]
+pub struct Mscons {
+    options: Options,
+}
+
+impl Mscons {
+    pub fn new(options: Options) -> Mscons {
+        Mscons { options }
+    }
+
+    /// Convert a single quantity into a message tagged by the layout.
+    fn convert(&self, layout: &Layout, context: &Context, value: Value) -> ConvertResult<Message> {
+        if let Some(e) = value.error {
+            return Err(e);
+        }
+        let field = match layout.types.contains_key(&layout.measure) {
+            true => layout.parse_value(&layout.measure, &value.quantity)?,
+            false => value.quantity.parse().map(FieldValue::Float).map_err(|_| {
+                ConvertError::Import(format!("Invalid quantity {}", value.quantity))
+            })?,
+        };
+        let missing = |name: &str| ConvertError::Import(format!("Quantity without {} time", name));
+        let start = value.start.ok_or_else(|| missing("start"))?;
+        let timestamp = match layout.time.as_str() {
+            "end" => value.end.ok_or_else(|| missing("end"))?,
+            _ => start,
+        };
+
+        let tags = layout
+            .tags
+            .iter()
+            .filter_map(|t| {
+                let v = match t.as_str() {
+                    "location" => context.location.clone(),
+                    "obis" => context.obis.clone(),
+                    "qualifier" => value.qualifier.clone(),
+                    "status" => value.status.join(","),
+                    "unit" => value.unit.clone(),
+                    "sender" => context.sender.clone(),
+                    "receiver" => context.receiver.clone(),
+                    "start" => start.to_rfc3339(),
+                    "end" => value.end.map(|e| e.to_rfc3339()).unwrap_or_default(),
+                    _ => String::new(),
+                };
+                match v.is_empty() {
+                    true => None,
+                    false => Some((t.clone(), v)),
+                }
+            })
+            .collect();
+
+        Ok(Message::new(
+            layout.series.clone(),
+            timestamp,
+            (layout.measure.clone(), field),
+            tags,
+        ))
+    }
+
+    /// Read a single file and pass its quantities to the sink.
+    /// The file `-` is read from stdin.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let reader = source::open(file, layout.encoding.as_deref())?;
+        self.import_reader(file, reader, layout, sink)
+    }
+
+    /// Parse the interchange of some reader and pass the quantities
+    /// to the sink. Rows are counted in segments, rejected quantities
+    /// are reported with the segment of their `QTY`.
+    pub fn import_reader<R: BufRead>(
+        &self,
+        source: &str,
+        mut reader: R,
+        layout: &Layout,
+        sink: &dyn Sink,
+    ) -> ConvertResult<()> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut driver = Driver::new(&self.options, source, sink);
+        let mut context = Context::default();
+        let mut pending: Option<Value> = None;
+
+        let mut flush = |context: &Context, pending: &mut Option<Value>| {
+            if let Some(value) = pending.take() {
+                driver.row(value.row, || Some(self.convert(layout, context, value)));
+            }
+        };
+
+        for (i, segment) in segments(&text).into_iter().enumerate() {
+            let row = i + 1;
+            match segment.tag() {
+                "QTY" | "LIN" | "LOC" | "UNS" | "UNH" | "UNT" | "UNZ" => {
+                    flush(&context, &mut pending)
+                }
+                _ => (),
+            }
+            match segment.tag() {
+                "UNH" => {
+                    context = Context::default();
+                    if segment.get(2, 0) != "MSCONS" {
+                        let err = ConvertError::Import(format!(
+                            "Message {} is not supported",
+                            segment.get(2, 0)
+                        ));
+                        sink.reject(source, row, err);
+                    }
+                }
+                "NAD" if segment.get(1, 0) == "MS" => context.sender = segment.get(2, 0).into(),
+                "NAD" if segment.get(1, 0) == "MR" => context.receiver = segment.get(2, 0).into(),
+                "LOC" if segment.get(1, 0) == "172" => {
+                    context.location = segment.get(2, 0).into();
+                    context.obis.clear();
+                }
+                "PIA" if segment.get(1, 0) == "5" => context.obis = segment.get(2, 0).into(),
+                "QTY" => {
+                    pending = Some(Value {
+                        row,
+                        qualifier: segment.get(1, 0).into(),
+                        quantity: segment.get(1, 1).into(),
+                        unit: segment.get(1, 2).into(),
+                        ..Value::default()
+                    })
+                }
+                "DTM" => if let Some(ref mut value) = pending {
+                    let t = time(layout, segment.get(1, 1), segment.get(1, 2));
+                    match (segment.get(1, 0), t) {
+                        ("163", Ok(t)) => value.start = Some(t),
+                        ("164", Ok(t)) => value.end = Some(t),
+                        ("163", Err(e)) | ("164", Err(e)) => value.error = Some(e),
+                        _ => (),
+                    }
+                },
+                "STS" => if let Some(ref mut value) = pending {
+                    let reason = match segment.get(3, 0) {
+                        "" => segment.get(2, 0),
+                        r => r,
+                    };
+                    if !reason.is_empty() {
+                        value.status.push(reason.into());
+                    }
+                },
+                _ => (),
+            }
+        }
+        flush(&context, &mut pending);
+
+        driver.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mapper::test::Collect;
+
+    const MSCONS: &str = "UNA:+,? '
+UNB+UNOC:3+9900000000003:500+9900000000010:500+160102:0100+REF1'
+UNH+1+MSCONS:D:04B:UN:2.4c'
+BGM+7+MSI5422+9'
+DTM+137:201601020100?+01:303'
+NAD+MS+9900000000003::293'
+NAD+MR+9900000000010::293'
+UNS+D'
+NAD+DP'
+LOC+172+DE00014545768S0000000000000003054'
+LIN+1'
+PIA+5+1-1?:1.29.0:SRW'
+QTY+220:108,5:KWH'
+DTM+163:201601010000?+01:303'
+DTM+164:201601010015?+01:303'
+QTY+67:103,5'
+DTM+163:201601010015?+01:303'
+DTM+164:201601010030?+01:303'
+STS+Z33++Z83'
+QTY+220:1'
+DTM+163:20160101:999'
+UNT+19+1'
+UNZ+1+REF1'
+";
+
+    #[test]
+    fn test_segments() {
+        let segments = segments(MSCONS);
+        assert_eq!(segments.len(), 22);
+        assert_eq!(segments[0].tag(), "UNB");
+        assert_eq!(segments[10].get(2, 0), "1-1:1.29.0");
+        assert_eq!(segments[11].get(1, 1), "108.5");
+        assert_eq!(segments[12].get(1, 1), "201601010000+01");
+        assert_eq!(segments[12].get(5, 5), "");
+
+        let plain = super::segments("UNH+1+MSCONS'QTY+220:1.5'");
+        assert_eq!(plain[1].get(1, 1), "1.5");
+    }
+
+    #[test]
+    fn test_time() {
+        let layout = Layout {
+            timezone: Some("Europe/Berlin".into()),
+            ..Layout::default()
+        };
+        let t = |v, f| time(&layout, v, f).map(|t| t.to_rfc3339());
+        assert_eq!(t("201601010015+01", "303").unwrap(), "2015-12-31T23:15:00+00:00");
+        assert_eq!(t("201607010000+0200", "303").unwrap(), "2016-06-30T22:00:00+00:00");
+        assert_eq!(t("201607010000", "203").unwrap(), "2016-06-30T22:00:00+00:00");
+        assert_eq!(t("20160101", "102").unwrap(), "2015-12-31T23:00:00+00:00");
+        assert!(t("20160101", "999").is_err());
+        assert!(t("2016", "303").is_err());
+    }
+
+    #[test]
+    fn test_import() {
+        let layout = Layout {
+            measure: "kWh".into(),
+            tags: vec!["location".into(), "obis".into(), "qualifier".into(), "status".into()],
+            ..Layout::default()
+        };
+        let sink = Collect::default();
+        Mscons::default()
+            .import_reader("profile.edi", MSCONS.as_bytes(), &layout, &sink)
+            .unwrap();
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].time().to_rfc3339(), "2015-12-31T23:00:00+00:00");
+        assert_eq!(sent[0].value(), &("kWh".to_string(), FieldValue::Float(108.5)));
+        assert_eq!(
+            sent[0].tags(),
+            &[
+                ("location".to_string(), "DE00014545768S0000000000000003054".to_string()),
+                ("obis".to_string(), "1-1:1.29.0".to_string()),
+                ("qualifier".to_string(), "220".to_string()),
+            ]
+        );
+        assert_eq!(sent[1].tags()[2], ("qualifier".into(), "67".into()));
+        assert_eq!(sent[1].tags()[3], ("status".into(), "Z83".into()));
+        assert_eq!(*sink.rejected.borrow(), vec![19]);
+
+        // resume after the first quantity and use the end of the interval
+        let options = Options {
+            limit: Some(1),
+            resume: vec![("profile.edi".to_string(), 12)].into_iter().collect(),
+        };
+        let end = Layout {
+            time: "end".into(),
+            ..layout
+        };
+        let sink = Collect::default();
+        Mscons::new(options)
+            .import_reader("profile.edi", MSCONS.as_bytes(), &end, &sink)
+            .unwrap();
+        let sent = sink.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].time().to_rfc3339(), "2015-12-31T23:30:00+00:00");
+        assert!(sink.rejected.borrow().is_empty());
+    }
+}
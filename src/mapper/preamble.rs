@@ -0,0 +1,226 @@
+//! Extract metadata from the preamble rows before the header.
+//! A value is taken by its row and column or by a regular
+//! expression and either added as static tag to every point
+//! of the file or used for some setting of the layout.
+//!
+//! ```toml
+//! [[preamble]]
+//! tag = "meter"
+//! row = 0
+//! column = 0
+//!
+//! [[preamble]]
+//! setting = "timezone"
+//! regex = "^(CET|UTC|Europe/\\w+)"
+//! ```
+use regex::Regex;
+use super::Layout;
+use error::{ConvertError, ConvertResult};
+
+/// Layout settings which can be read from the preamble.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Setting {
+    Series,
+    Timezone,
+}
+
+/// A single value of the preamble.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Extract {
+    /// Add the value as tag with this name.
+    pub tag: Option<String>,
+    /// Use the value for this layout setting.
+    pub setting: Option<Setting>,
+    /// Row of the preamble counted from 0, all rows if not set.
+    pub row: Option<usize>,
+    /// Column of the row counted from 0, the whole row if not set.
+    pub column: Option<usize>,
+    /// Use the first group or the whole match of the
+    /// first row or cell matching this expression.
+    pub regex: Option<String>,
+}
+
+impl Extract {
+    /// Return an error if the extraction is not usable.
+    pub fn check(&self) -> ConvertResult<()> {
+        if self.tag.is_some() == self.setting.is_some() {
+            return Err(ConvertError::Config(
+                "A preamble value needs either a tag or a setting".into(),
+            ));
+        }
+        if self.row.is_none() && self.regex.is_none() {
+            return Err(ConvertError::Config(
+                "A preamble value needs a row or a regex".into(),
+            ));
+        }
+        self.regex().map(|_| ())
+    }
+
+    fn regex(&self) -> ConvertResult<Option<Regex>> {
+        match self.regex {
+            None => Ok(None),
+            Some(ref r) => Regex::new(r)
+                .map(Some)
+                .map_err(|e| ConvertError::Config(format!("Invalid regex {}: {}", r, e))),
+        }
+    }
+
+    fn name(&self) -> String {
+        match (&self.tag, self.setting) {
+            (Some(t), _) => format!("tag {}", t),
+            (None, Some(s)) => format!("setting {:?}", s).to_lowercase(),
+            (None, None) => "nothing".into(),
+        }
+    }
+
+    /// Return the value from the preamble rows of some file.
+    pub fn extract(&self, rows: &[String], delimiter: char) -> ConvertResult<String> {
+        let regex = self.regex()?;
+        let candidates: Vec<&String> = match self.row {
+            Some(r) => rows.get(r).into_iter().collect(),
+            None => rows.iter().collect(),
+        };
+
+        let value = candidates
+            .iter()
+            .filter_map(|row| match self.column {
+                Some(c) => row.split(delimiter).nth(c),
+                None => Some(row.as_str()),
+            })
+            .filter_map(|cell| match regex {
+                None => Some(cell),
+                Some(ref r) => r
+                    .captures(cell)
+                    .and_then(|c| c.get(1).or_else(|| c.get(0)))
+                    .map(|m| m.as_str()),
+            })
+            .map(|v| v.trim())
+            .next();
+
+        value.map(|v| v.to_owned()).ok_or_else(|| {
+            ConvertError::Import(format!("No preamble value found for {}", self.name()))
+        })
+    }
+}
+
+/// Apply the preamble extractions of the layout to the preamble rows.
+/// Returns the layout with the extracted settings and the static tags.
+pub fn apply(layout: &Layout, rows: &[String]) -> ConvertResult<(Layout, Vec<(String, String)>)> {
+    let mut file = layout.clone();
+    let mut tags = vec![];
+    for extract in &layout.preamble {
+        let value = extract.extract(rows, layout.delimiter)?;
+        match (&extract.tag, extract.setting) {
+            (Some(t), _) => tags.push((t.clone(), value)),
+            (None, Some(Setting::Series)) => file.series = value,
+            (None, Some(Setting::Timezone)) => {
+                file.timezone = Some(value);
+                file.timezone()?;
+            }
+            (None, None) => (),
+        }
+    }
+    Ok((file, tags))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rows() -> Vec<String> {
+        vec!["1,53E+07 ;;;", "kWh ;;;Legende Status:", " ;;;220", "CET ;;;"]
+            .into_iter()
+            .map(|r| r.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_extract() {
+        let meter = Extract {
+            tag: Some("meter".into()),
+            row: Some(0),
+            column: Some(0),
+            ..Extract::default()
+        };
+        assert_eq!(meter.extract(&rows(), ';').unwrap(), "1,53E+07");
+
+        let status = Extract {
+            tag: Some("status".into()),
+            column: Some(3),
+            regex: Some(r"^\d+$".into()),
+            ..Extract::default()
+        };
+        assert_eq!(status.extract(&rows(), ';').unwrap(), "220");
+
+        let unit = Extract {
+            tag: Some("unit".into()),
+            regex: Some(r"^(\w+) ;;;Legende".into()),
+            ..Extract::default()
+        };
+        assert_eq!(unit.extract(&rows(), ';').unwrap(), "kWh");
+
+        let missing = Extract {
+            row: Some(10),
+            ..meter
+        };
+        assert!(missing.extract(&rows(), ';').is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let both = Extract {
+            tag: Some("meter".into()),
+            setting: Some(Setting::Series),
+            row: Some(0),
+            ..Extract::default()
+        };
+        assert!(both.check().is_err());
+        assert!(Extract { setting: None, ..both.clone() }.check().is_ok());
+        assert!(Extract { row: None, setting: None, ..both.clone() }.check().is_err());
+
+        let regex = Extract {
+            tag: Some("meter".into()),
+            regex: Some("(a".into()),
+            ..Extract::default()
+        };
+        assert!(regex.check().is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let layout = Layout {
+            delimiter: ';',
+            preamble: vec![
+                Extract {
+                    tag: Some("meter".into()),
+                    row: Some(0),
+                    column: Some(0),
+                    ..Extract::default()
+                },
+                Extract {
+                    setting: Some(Setting::Timezone),
+                    row: Some(3),
+                    column: Some(0),
+                    ..Extract::default()
+                },
+            ],
+            ..Layout::default()
+        };
+        let (file, tags) = apply(&layout, &rows()).unwrap();
+        assert_eq!(file.timezone, Some("CET".into()));
+        assert_eq!(tags, vec![("meter".to_string(), "1,53E+07".to_string())]);
+
+        let layout = Layout {
+            preamble: vec![Extract {
+                setting: Some(Setting::Timezone),
+                row: Some(1),
+                column: Some(0),
+                ..Extract::default()
+            }],
+            ..layout
+        };
+        assert!(apply(&layout, &rows()).is_err());
+    }
+}
@@ -0,0 +1,418 @@
+//! Import snapshots of prometheus metrics in the text exposition
+//! or OpenMetrics format.
+//!
+//! Each metric family becomes a measurement with the field `value`,
+//! or a field of the series of the layout with `metric_fields`. The
+//! samples of histograms and summaries are expanded into the fields
+//! `sum`, `count`, `bucket_<le>` and `quantile_<q>`. Labels become
+//! tags, only the ones named by the layout if it names any.
+//!
+//! Timestamps of samples are read as milliseconds, small or fractional
+//! ones as seconds like in OpenMetrics. Samples without timestamp get
+//! the `default_time` parsed with the time format of the layout or the
+//! modification time of the file. Values which influxdb can't store
+//! like `NaN` and `+Inf` are skipped.
+//!
+//! ```toml
+//! format = "prometheus"
+//! series = "app"
+//! tags = ["instance", "method"]
+//!
+//! [prometheus]
+//! metric_fields = true
+//! ```
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufRead;
+
+use chrono::{DateTime, Utc};
+use client::{FieldValue, Message};
+use super::{Driver, Layout, Options, Sink};
+use super::source;
+use error::{ConvertError, ConvertResult};
+
+/// Timestamps below are read as seconds, a millisecond
+/// timestamp this small would be from early 1973.
+const SECONDS: f64 = 1e11;
+
+/// Suffixes of the samples of some metric family.
+const SUFFIXES: &[&str] = &["_bucket", "_sum", "_count", "_total", "_created", "_gsum", "_gcount"];
+
+/// A single sample line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+    /// Timestamp in milliseconds.
+    pub time: Option<i64>,
+}
+
+/// An import error of some invalid line.
+fn invalid(msg: String) -> ConvertError {
+    ConvertError::Import(msg)
+}
+
+/// Read the quoted and escaped value of some label.
+fn quoted(rest: &str) -> ConvertResult<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, c)) => value.push(c),
+                None => break,
+            },
+            '"' => return Ok((value, &rest[i + 1..])),
+            c => value.push(c),
+        }
+    }
+    Err(invalid("Unterminated label value".into()))
+}
+
+/// Parse the labels after the opening brace up to the closing one.
+fn labels(mut rest: &str) -> ConvertResult<(Vec<(String, String)>, &str)> {
+    let mut labels = vec![];
+    loop {
+        rest = rest.trim_start().trim_start_matches(',').trim_start();
+        if let Some(r) = rest.strip_prefix('}') {
+            return Ok((labels, r));
+        }
+        let eq = rest
+            .find('=')
+            .ok_or_else(|| invalid(format!("Invalid labels {}", rest)))?;
+        let name = rest[..eq].trim().to_owned();
+        let value = rest[eq + 1..]
+            .trim_start()
+            .strip_prefix('"')
+            .ok_or_else(|| invalid(format!("Label {} is not quoted", name)))?;
+        let (value, r) = quoted(value)?;
+        labels.push((name, value));
+        rest = r;
+    }
+}
+
+/// Parse the timestamp of some sample into milliseconds.
+fn timestamp(raw: &str) -> ConvertResult<i64> {
+    let t: f64 = raw
+        .parse()
+        .map_err(|_| invalid(format!("Invalid timestamp {}", raw)))?;
+    match raw.contains('.') || t.abs() < SECONDS {
+        true => Ok((t * 1000.0).round() as i64),
+        false => Ok(t as i64),
+    }
+}
+
+/// Parse a single sample line.
+pub fn parse(line: &str) -> ConvertResult<Sample> {
+    // exemplars of OpenMetrics are ignored
+    let line = line.split(" # ").next().unwrap_or(line);
+    let end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .unwrap_or(line.len());
+    let name = &line[..end];
+    if name.is_empty() {
+        return Err(invalid("Missing metric name".into()));
+    }
+    let (labels, rest) = match line[end..].trim_start().strip_prefix('{') {
+        Some(r) => labels(r)?,
+        None => (vec![], &line[end..]),
+    };
+
+    let mut parts = rest.split_whitespace();
+    let raw = parts
+        .next()
+        .ok_or_else(|| invalid(format!("Missing value of {}", name)))?;
+    let value = match raw {
+        "+Inf" | "Inf" => f64::INFINITY,
+        "-Inf" => f64::NEG_INFINITY,
+        r => r
+            .parse()
+            .map_err(|_| invalid(format!("Invalid value {}", r)))?,
+    };
+    let time = parts.next().map(timestamp).transpose()?;
+    if let Some(extra) = parts.next() {
+        return Err(invalid(format!("Unexpected {}", extra)));
+    }
+    Ok(Sample {
+        name: name.to_owned(),
+        labels,
+        value,
+        time,
+    })
+}
+
+/// The metric family of some sample and the field it is written to.
+/// Returns none for the field of plain values.
+fn family(types: &HashMap<String, String>, sample: &mut Sample) -> (String, Option<String>) {
+    let label = |sample: &mut Sample, name: &str| {
+        let pos = sample.labels.iter().position(|l| l.0 == name)?;
+        Some(sample.labels.remove(pos).1)
+    };
+    if types.get(&sample.name).map(|t| t.as_str()) == Some("summary") {
+        let field = label(sample, "quantile").map(|q| format!("quantile_{}", q));
+        return (sample.name.clone(), field);
+    }
+    if types.contains_key(&sample.name) {
+        return (sample.name.clone(), None);
+    }
+    for suffix in SUFFIXES {
+        let base = match sample.name.strip_suffix(suffix) {
+            Some(b) if types.contains_key(b) => b.to_owned(),
+            _ => continue,
+        };
+        let field = match *suffix {
+            "_total" => None,
+            "_bucket" => label(sample, "le").map(|le| format!("bucket_{}", le)),
+            s => Some(s[1..].to_owned()),
+        };
+        return (base, field);
+    }
+    (sample.name.clone(), None)
+}
+
+/// A convenient struct to import prometheus metrics.
+#[derive(Debug, Default)]
+pub struct Prometheus {
+    options: Options,
+}
+
+impl Prometheus {
+    pub fn new(options: Options) -> Prometheus {
+        Prometheus { options }
+    }
+
+    /// Convert a sample into a message, none if influxdb can't store its value.
+    fn convert(
+        &self,
+        layout: &Layout,
+        types: &HashMap<String, String>,
+        mut sample: Sample,
+        time: DateTime<Utc>,
+    ) -> ConvertResult<Option<Message>> {
+        let (family, field) = family(types, &mut sample);
+        let (series, field) = match (layout.prometheus.metric_fields, field) {
+            (true, None) => (layout.series.clone(), family),
+            (true, Some(f)) => (layout.series.clone(), format!("{}_{}", family, f)),
+            (false, f) => (family, f.unwrap_or_else(|| "value".into())),
+        };
+        let value = match layout.types.contains_key(&field) {
+            true => layout.parse_value(&field, &sample.value.to_string())?,
+            false if sample.value.is_finite() => FieldValue::Float(sample.value),
+            false => return Ok(None),
+        };
+        let time = match sample.time {
+            Some(ms) => DateTime::from_timestamp_millis(ms)
+                .ok_or_else(|| invalid(format!("Invalid timestamp {}", ms)))?,
+            None => time,
+        };
+        let tags = sample
+            .labels
+            .into_iter()
+            .filter(|l| layout.tags.is_empty() || layout.tags.contains(&l.0))
+            .collect();
+
+        Ok(Some(Message::new(series, time, (field, value), tags)))
+    }
+
+    /// Read a single file and pass its samples to the sink. Samples
+    /// without timestamp get the modification time of the file unless
+    /// the layout has a default time. The file `-` is read from stdin.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let time = match layout.prometheus.default_time {
+            Some(ref t) => layout.parse_time(t)?,
+            None => fs::metadata(file)
+                .and_then(|m| m.modified())
+                .map(DateTime::from)
+                .unwrap_or_else(|_| Utc::now()),
+        };
+        let reader = source::open(file, layout.encoding.as_deref())?;
+        self.read(file, reader, layout, time, sink)
+    }
+
+    /// Read the samples of some reader and pass them to the sink.
+    /// Samples without timestamp get the default time of the
+    /// layout or the current time.
+    pub fn import_reader<R: BufRead>(
+        &self,
+        source: &str,
+        reader: R,
+        layout: &Layout,
+        sink: &dyn Sink,
+    ) -> ConvertResult<()> {
+        let time = match layout.prometheus.default_time {
+            Some(ref t) => layout.parse_time(t)?,
+            None => Utc::now(),
+        };
+        self.read(source, reader, layout, time, sink)
+    }
+
+    /// Read the samples with the time of samples without timestamp.
+    fn read<R: BufRead>(
+        &self,
+        source: &str,
+        reader: R,
+        layout: &Layout,
+        time: DateTime<Utc>,
+        sink: &dyn Sink,
+    ) -> ConvertResult<()> {
+        let mut driver = Driver::new(&self.options, source, sink);
+        let mut types = HashMap::new();
+        let mut skipped = 0;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let trimmed = line.trim();
+            // the types are needed for the lines after some resumed row
+            if let Some(comment) = trimmed.strip_prefix('#') {
+                let mut words = comment.split_whitespace();
+                if let (Some("TYPE"), Some(name), Some(kind)) = (words.next(), words.next(), words.next()) {
+                    types.insert(name.to_owned(), kind.to_lowercase());
+                }
+                continue;
+            }
+            let read = driver.row(i + 1, || {
+                if trimmed.is_empty() {
+                    return None;
+                }
+                match parse(trimmed).and_then(|s| self.convert(layout, &types, s, time)) {
+                    Ok(Some(msg)) => Some(Ok(msg)),
+                    Ok(None) => {
+                        skipped += 1;
+                        None
+                    }
+                    Err(e) => Some(Err(e)),
+                }
+            });
+            if !read {
+                break;
+            }
+        }
+
+        if skipped > 0 {
+            info!(format!("{}: {} samples without finite value skipped", source, skipped));
+        }
+        driver.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mapper::test::Collect;
+    use mapper::PrometheusLayout;
+
+    const METRICS: &str = r#"# HELP http_requests_total The total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+http_requests_total{method="post",code="400",} 3 1395066363000
+# TYPE rpc_duration_seconds summary
+rpc_duration_seconds{quantile="0.5"} 4773
+rpc_duration_seconds_sum 1.7560473e+07
+# TYPE request_seconds histogram
+request_seconds_bucket{le="0.05"} 24054
+request_seconds_bucket{le="+Inf"} 144320
+request_seconds_count 144320
+temperature{room="a \"big\" one"} NaN
+broken{ 1
+"#;
+
+    #[test]
+    fn test_parse() {
+        let s = parse(r#"msdos_file_access_time_seconds{path="C:\\DIR\\FILE.TXT",error="Cannot find file:\n\"FILE.TXT\""} 1.458255915e9"#)
+            .unwrap();
+        assert_eq!(s.labels[0].1, r"C:\DIR\FILE.TXT");
+        assert_eq!(s.labels[1].1, "Cannot find file:\n\"FILE.TXT\"");
+        assert_eq!(s.value, 1.458255915e9);
+        assert_eq!(s.time, None);
+
+        let s = parse("metric_without_labels 12.47 1395066363000").unwrap();
+        assert_eq!(s.time, Some(1395066363000));
+        let s = parse(r#"acme_total{id="1"} 3 1520879607.789 # {trace_id="KOO5S4vxi0o"} 0.67"#).unwrap();
+        assert_eq!(s.time, Some(1520879607789));
+        assert_eq!(parse("up -Inf").unwrap().value, f64::NEG_INFINITY);
+
+        assert!(parse("up").is_err());
+        assert!(parse("up x").is_err());
+        assert!(parse(r#"up{job="a} 1"#).is_err());
+        assert!(parse("up 1 2 3").is_err());
+    }
+
+    #[test]
+    fn test_import() {
+        let layout = Layout {
+            prometheus: PrometheusLayout {
+                default_time: Some("2016-10-18 12:00:00".into()),
+                ..PrometheusLayout::default()
+            },
+            ..Layout::default()
+        };
+        let sink = Collect::default();
+        Prometheus::default()
+            .import_reader("metrics.prom", METRICS.as_bytes(), &layout, &sink)
+            .unwrap();
+        {
+            let sent = sink.sent.borrow();
+            assert_eq!(sent.len(), 7);
+            assert_eq!(sent[0].series(), "http_requests_total");
+            assert_eq!(sent[0].value(), &("value".to_string(), FieldValue::Float(1027.0)));
+            assert_eq!(sent[0].time().timestamp(), 1395066363);
+            assert_eq!(sent[1].tags()[1], ("code".to_string(), "400".to_string()));
+            assert_eq!(sent[2].value().0, "quantile_0.5");
+            assert!(sent[2].tags().is_empty());
+            assert_eq!(sent[2].time().to_rfc3339(), "2016-10-18T12:00:00+00:00");
+            assert_eq!(sent[3].value().0, "sum");
+            assert_eq!(sent[5].series(), "request_seconds");
+            assert_eq!(sent[5].value().0, "bucket_+Inf");
+            assert_eq!(*sink.rejected.borrow(), vec![13]);
+        }
+
+        // metrics as fields with selected labels
+        let fields = Layout {
+            series: "app".into(),
+            tags: vec!["method".into()],
+            prometheus: PrometheusLayout {
+                metric_fields: true,
+                ..layout.prometheus.clone()
+            },
+            ..layout
+        };
+        let options = Options {
+            resume: vec![("metrics.prom".to_string(), 7)].into_iter().collect(),
+            ..Options::default()
+        };
+        let sink = Collect::default();
+        Prometheus::new(options.clone())
+            .import_reader("metrics.prom", METRICS.as_bytes(), &fields, &sink)
+            .unwrap();
+        {
+            let sent = sink.sent.borrow();
+            assert_eq!(sent.len(), 3);
+            assert_eq!(sent[0].series(), "app");
+            assert_eq!(sent[0].value().0, "request_seconds_bucket_0.05");
+            assert_eq!(sent[2].value().0, "request_seconds_count");
+        }
+
+        // the limit counts the lines after the resumed ones
+        let options = Options {
+            limit: Some(2),
+            ..options
+        };
+        let sink = Collect::default();
+        Prometheus::new(options)
+            .import_reader("metrics.prom", METRICS.as_bytes(), &fields, &sink)
+            .unwrap();
+        assert_eq!(sink.sent.borrow().len(), 2);
+        assert_eq!(sink.sent.borrow()[1].value().0, "request_seconds_bucket_+Inf");
+
+        // millisecond timestamps are kept
+        let data = "up 1 1520879607789\n";
+        let sink = Collect::default();
+        Prometheus::default()
+            .import_reader("metrics.prom", data.as_bytes(), &Layout::default(), &sink)
+            .unwrap();
+        assert_eq!(sink.sent.borrow()[0].time().timestamp_millis(), 1520879607789);
+    }
+}
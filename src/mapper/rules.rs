@@ -0,0 +1,108 @@
+//! Rules select the layout of some file by its name.
+//! A rule either matches a glob or a regular expression.
+//! Patterns without a path separator are matched against
+//! the file name, all others against the whole path.
+use std::path::Path;
+
+use glob::Pattern;
+use regex::Regex;
+use super::Layout;
+use error::{ConvertError, ConvertResult};
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+/// Apply `layout` to all files matching some pattern.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    matcher: Matcher,
+    pub layout: Layout,
+}
+
+impl Rule {
+    /// Create a rule from a shell glob like `*_lastgang.csv`.
+    pub fn glob(pattern: &str, layout: Layout) -> ConvertResult<Rule> {
+        Pattern::new(pattern)
+            .map(|p| Rule {
+                matcher: Matcher::Glob(p),
+                layout,
+            })
+            .map_err(|e| ConvertError::Config(format!("Invalid glob {}: {}", pattern, e)))
+    }
+
+    /// Create a rule from a regular expression like `^wetter_.*\.txt$`.
+    pub fn regex(pattern: &str, layout: Layout) -> ConvertResult<Rule> {
+        Regex::new(pattern)
+            .map(|r| Rule {
+                matcher: Matcher::Regex(r),
+                layout,
+            })
+            .map_err(|e| ConvertError::Config(format!("Invalid regex {}: {}", pattern, e)))
+    }
+
+    /// Whether the rule applies to the file.
+    pub fn matches(&self, file: &str) -> bool {
+        let pattern = match self.matcher {
+            Matcher::Glob(ref p) => p.as_str(),
+            Matcher::Regex(ref r) => r.as_str(),
+        };
+        let name = match pattern.contains('/') {
+            true => Some(file),
+            false => Path::new(file).file_name().and_then(|n| n.to_str()),
+        };
+
+        name.is_some_and(|n| match self.matcher {
+            Matcher::Glob(ref p) => p.matches(n),
+            Matcher::Regex(ref r) => r.is_match(n),
+        })
+    }
+}
+
+/// Return the layout of the first rule matching the file.
+pub fn select<'a>(rules: &'a [Rule], file: &str) -> Option<&'a Layout> {
+    rules.iter().find(|r| r.matches(file)).map(|r| &r.layout)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn layout(series: &str) -> Layout {
+        Layout {
+            series: series.into(),
+            ..Layout::default()
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let rule = Rule::glob("*_lastgang.csv", layout("")).unwrap();
+        assert!(rule.matches("drop/4711_lastgang.csv"));
+        assert!(!rule.matches("drop/4711_lastgang.txt"));
+
+        let rule = Rule::regex(r"^wetter_.*\.txt$", layout("")).unwrap();
+        assert!(rule.matches("drop/wetter_berlin.txt"));
+        assert!(!rule.matches("wetter/berlin.txt"));
+
+        let rule = Rule::glob("archive/*.csv", layout("")).unwrap();
+        assert!(rule.matches("archive/pv_1.csv"));
+        assert!(!rule.matches("drop/pv_1.csv"));
+
+        assert!(Rule::glob("[a", layout("")).is_err());
+        assert!(Rule::regex("(a", layout("")).is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        let rules = vec![
+            Rule::glob("pv_*.csv", layout("pv")).unwrap(),
+            Rule::regex(r"\.csv$", layout("other")).unwrap(),
+        ];
+        assert_eq!(select(&rules, "drop/pv_1.csv").unwrap().series, "pv");
+        assert_eq!(select(&rules, "drop/x_1.csv").unwrap().series, "other");
+        assert!(select(&rules, "drop/x_1.txt").is_none());
+    }
+}
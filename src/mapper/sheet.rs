@@ -0,0 +1,332 @@
+//! Read the sheets of xlsx, xls and ods workbooks.
+//!
+//! The rows of a sheet are read like the lines of a csv file,
+//! the header is searched after the skipped rows. Dates of the
+//! time column are taken as they are in the timezone of the layout.
+//! Other numbers and dates are written with the decimal separator
+//! and the time format of the layout, so they are parsed like csv
+//! cells. Rows are counted over all selected sheets of a workbook.
+//!
+//! ```toml
+//! format = "sheet"
+//! skip_rows = 2
+//!
+//! [sheet]
+//! name = "*"
+//! tag = "sheet"
+//! ```
+use std::fmt::Write;
+use std::fs::File;
+use std::io::BufReader;
+
+use calamine::{open_workbook_auto, Data, Reader, Sheets};
+use chrono::{NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use super::csv::{convert_cells, convert_timed, find_columns};
+use super::{preamble, Driver, Layout, Options, Sink};
+use error::{ConvertError, ConvertResult};
+
+/// A workbook opened from some file.
+pub type Workbook = Sheets<BufReader<File>>;
+
+/// A convenient struct to import the sheets of workbooks.
+///
+/// The header of each sheet is searched like the one
+/// of csv files, the rows are counted over all sheets.
+#[derive(Debug, Default)]
+pub struct Sheet {
+    options: Options,
+}
+
+impl Sheet {
+    pub fn new(options: Options) -> Sheet {
+        Sheet { options }
+    }
+
+    /// Read the selected sheets of some workbook and pass their rows
+    /// to the sink.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let mut workbook = open(file)?;
+        let delimiter = layout.delimiter.to_string();
+        let mut driver = Driver::new(&self.options, file, sink);
+        let (mut offset, mut more) = (0, true);
+
+        for name in select(&workbook, layout)? {
+            if !more {
+                break;
+            }
+            let rows = rows(&mut workbook, &name)?;
+            let start = offset;
+            offset += rows.len();
+
+            let mut rows = rows.into_iter();
+            let preamble: Vec<String> = rows
+                .by_ref()
+                .take(layout.skip_rows)
+                .map(|r| cells(layout, &r).join(&delimiter))
+                .collect();
+            let header = rows
+                .next()
+                .ok_or_else(|| ConvertError::Import(format!("Header not found in sheet {}", name)))
+                .and_then(|h| find_columns(layout, &cells(layout, &h)))
+                .and_then(|h| {
+                    preamble::apply(layout, &preamble).map(|(l, statics)| (l, statics, h))
+                });
+            let (layout, mut statics, header) = match header {
+                Err(e) => {
+                    sink.reject(file, start + layout.skip_rows + 1, e);
+                    continue;
+                }
+                Ok(h) => h,
+            };
+            if let Some(ref tag) = layout.sheet.tag {
+                statics.push((tag.clone(), name.clone()));
+            }
+
+            for (i, r) in rows.enumerate() {
+                let row = start + layout.skip_rows + i + 2;
+                more = driver.row(row, || {
+                    let texts = cells(&layout, &r);
+                    Some(match r.get(header.1).and_then(date) {
+                        Some(t) => layout
+                            .local_time(&t)
+                            .and_then(|t| convert_timed(&layout, &header, &statics, &texts, t)),
+                        None => convert_cells(&layout, &header, &statics, &texts),
+                    })
+                });
+                if !more {
+                    break;
+                }
+            }
+        }
+        driver.finish();
+        Ok(())
+    }
+}
+
+/// Open some workbook, the type is detected by the file extension.
+pub fn open(file: &str) -> ConvertResult<Workbook> {
+    open_workbook_auto(file).map_err(|e| ConvertError::Import(format!("{}: {}", file, e)))
+}
+
+/// The names of the sheets selected by the layout.
+pub fn select(workbook: &Workbook, layout: &Layout) -> ConvertResult<Vec<String>> {
+    let names = workbook.sheet_names();
+    match layout.sheet.name.as_deref() {
+        Some("*") => Ok(names),
+        Some(name) => names
+            .into_iter()
+            .find(|n| n == name)
+            .map(|n| vec![n])
+            .ok_or_else(|| ConvertError::Import(format!("Sheet {} not found", name))),
+        None => names
+            .into_iter()
+            .next()
+            .map(|n| vec![n])
+            .ok_or_else(|| ConvertError::Import("Workbook has no sheets".into())),
+    }
+}
+
+/// The rows of some sheet. Empty rows and columns before the
+/// first used cell are kept, so rows and columns are counted
+/// like they are shown.
+pub fn rows(workbook: &mut Workbook, name: &str) -> ConvertResult<Vec<Vec<Data>>> {
+    let range = workbook
+        .worksheet_range(name)
+        .map_err(|e| ConvertError::Import(format!("Sheet {}: {}", name, e)))?;
+    let (top, left) = range.start().unwrap_or((0, 0));
+    let mut rows = vec![vec![]; top as usize];
+    rows.extend(range.rows().map(|r| {
+        let mut row = vec![Data::Empty; left as usize];
+        row.extend_from_slice(r);
+        row
+    }));
+    Ok(rows)
+}
+
+/// Write some number with the decimal separator of the layout.
+fn number(layout: &Layout, value: f64) -> String {
+    value.to_string().replace('.', &layout.decimal_sep.to_string())
+}
+
+/// Write some date in the time format and timezone of the layout.
+fn time(layout: &Layout, naive: NaiveDateTime) -> String {
+    let tz = layout.timezone().ok().and_then(|t| t).unwrap_or(Tz::UTC);
+    let mut text = String::new();
+    match tz.from_local_datetime(&naive).earliest() {
+        Some(t) if write!(text, "{}", t.format(&layout.tformat)).is_ok() => text,
+        _ => naive.to_string(),
+    }
+}
+
+/// The date of some cell if it is stored as date.
+fn date(cell: &Data) -> Option<NaiveDateTime> {
+    match *cell {
+        Data::DateTime(ref d) if d.is_datetime() => d.as_datetime(),
+        Data::DateTimeIso(ref s) => iso(s),
+        _ => None,
+    }
+}
+
+/// Parse the dates of ods files with or without time.
+fn iso(value: &str) -> Option<NaiveDateTime> {
+    value
+        .parse::<NaiveDateTime>()
+        .ok()
+        .or_else(|| value.parse::<NaiveDate>().ok().map(|d| d.and_time(Default::default())))
+}
+
+/// The text of some cell as it would be written to a csv file.
+pub fn text(layout: &Layout, cell: &Data) -> String {
+    match *cell {
+        Data::Empty => String::new(),
+        Data::String(ref s) | Data::DurationIso(ref s) => s.clone(),
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) => number(layout, f),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(ref d) => match d.as_datetime() {
+            Some(t) if d.is_datetime() => time(layout, t),
+            _ => number(layout, d.as_f64()),
+        },
+        Data::DateTimeIso(ref s) => iso(s).map_or_else(|| s.clone(), |t| time(layout, t)),
+        Data::Error(ref e) => e.to_string(),
+    }
+}
+
+/// The texts of the cells of some row.
+pub fn cells(layout: &Layout, row: &[Data]) -> Vec<String> {
+    row.iter().map(|c| text(layout, c)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+    use client::FieldValue;
+    use mapper::test::Collect;
+    use mapper::SheetLayout;
+
+    #[test]
+    fn test_text() {
+        let layout = Layout {
+            tformat: "%d.%m.%Y %H:%M".into(),
+            timezone: Some("Europe/Berlin".into()),
+            decimal_sep: ',',
+            ..Layout::default()
+        };
+        // 2016-01-01 00:15 as excel serial date
+        let date = ExcelDateTime::new(42370.0 + 15.0 / 1440.0, ExcelDateTimeType::DateTime, false);
+        assert_eq!(text(&layout, &Data::DateTime(date)), "01.01.2016 00:15");
+        assert_eq!(text(&layout, &Data::DateTimeIso("2016-01-02".into())), "02.01.2016 00:00");
+        assert_eq!(text(&layout, &Data::Float(108.5)), "108,5");
+        assert_eq!(text(&layout, &Data::Float(3.0)), "3");
+        assert_eq!(text(&layout, &Data::Empty), "");
+
+        let utc = Layout {
+            tformat: "%+".into(),
+            ..Layout::default()
+        };
+        let t = utc.parse_time(&text(&utc, &Data::DateTime(date))).unwrap();
+        assert_eq!(t.to_rfc3339(), "2016-01-01T00:15:00+00:00");
+    }
+
+    #[test]
+    fn test_date() {
+        let layout = Layout {
+            tformat: "%d.%m.%Y %H:%M".into(),
+            timezone: Some("Europe/Berlin".into()),
+            ..Layout::default()
+        };
+        // 2016-01-01 00:15:30 keeps its seconds without the time format
+        let cell = ExcelDateTime::new(42370.0 + 930.0 / 86400.0, ExcelDateTimeType::DateTime, false);
+        let t = layout.local_time(&date(&Data::DateTime(cell)).unwrap()).unwrap();
+        assert_eq!(t.to_rfc3339(), "2015-12-31T23:15:30+00:00");
+
+        // skipped by the change to summer time
+        let gap = date(&Data::DateTimeIso("2016-03-27T02:30:00".into())).unwrap();
+        let err = layout.local_time(&gap).unwrap_err();
+        assert_eq!(format!("{}", err), "Date 2016-03-27 02:30:00 does not exist in Europe/Berlin.");
+        assert_eq!(date(&Data::Float(42370.0)), None);
+    }
+
+    #[test]
+    fn test_sheets() {
+        let layout: Layout = ::toml::from_str(
+            r#"
+            measure = "kWh"
+            tags = ["Status"]
+            tformat = "%d.%m.%Y %H:%M"
+            skip_rows = 1
+
+            [sheet]
+            name = "*"
+            tag = "sheet"
+
+            [types]
+            kWh = "float"
+
+            [[preamble]]
+            tag = "meter"
+            row = 0
+            regex = "\\d+"
+            "#,
+        ).unwrap();
+        let sheet = Sheet::default();
+        let sink = Collect::default();
+        sheet.import_file("assets/test.xlsx", &layout, &sink).unwrap();
+        {
+            let sent = sink.sent.borrow();
+            assert_eq!(sent.len(), 3);
+            assert_eq!(sent[0].time().to_rfc3339(), "2016-01-01T00:15:00+00:00");
+            assert_eq!(sent[0].value().1, FieldValue::Float(108.5));
+            assert_eq!(
+                sent[0].tags(),
+                &[
+                    ("meter".to_string(), "4711".to_string()),
+                    ("sheet".to_string(), "Zähler 1".to_string()),
+                    ("Status".to_string(), "W".to_string()),
+                ]
+            );
+            assert_eq!(sent[2].tags()[1], ("sheet".into(), "Zähler 2".into()));
+            assert_eq!(*sink.rejected.borrow(), vec![5]);
+        }
+
+        // rows are counted over the sheets
+        let sheet = Sheet::new(Options {
+            resume: vec![("assets/test.xlsx".to_string(), 5)].into_iter().collect(),
+            ..Options::default()
+        });
+        let sink = Collect::default();
+        sheet.import_file("assets/test.xlsx", &layout, &sink).unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+
+        // the limit counts the rows after the resumed ones of all sheets
+        let limited = Sheet::new(Options {
+            limit: Some(1),
+            resume: vec![("assets/test.xlsx".to_string(), 3)].into_iter().collect(),
+        });
+        let sink = Collect::default();
+        limited.import_file("assets/test.xlsx", &layout, &sink).unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(sink.sent.borrow()[0].tags()[1], ("sheet".into(), "Zähler 1".into()));
+
+        let first = Layout {
+            sheet: SheetLayout {
+                name: None,
+                ..layout.sheet.clone()
+            },
+            ..layout.clone()
+        };
+        let sink = Collect::default();
+        sheet.import_file("assets/test.xlsx", &first, &sink).unwrap();
+        assert!(sink.sent.borrow().is_empty());
+        let missing = Layout {
+            sheet: SheetLayout {
+                name: Some("Zähler 3".into()),
+                ..layout.sheet.clone()
+            },
+            ..layout
+        };
+        assert!(sheet.import_file("assets/test.xlsx", &missing, &sink).is_err());
+    }
+}
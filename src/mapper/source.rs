@@ -0,0 +1,187 @@
+//! Open the input of some mapper as utf-8 text.
+//!
+//! The file `-` is read from stdin.
+//! Files compressed with gzip, bzip2, xz or zstd are detected
+//! by their magic bytes or extension and decompressed while reading.
+//!
+//! The encoding is either given by its label, e.g. `windows-1252`,
+//! or detected from the first bytes of the input. A byte order mark
+//! takes precedence and is removed. Input which is no valid utf-8
+//! is read as Windows-1252, a superset of Latin-1.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use error::{ConvertError, ConvertResult};
+
+/// Number of bytes used to detect the encoding.
+const SNIFF: usize = 64 * 1024;
+
+/// A reader of decoded text.
+pub type Source = BufReader<Box<dyn Read>>;
+
+/// The compression formats which are read transparently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression from the first bytes of some input.
+    fn from_magic(bytes: &[u8]) -> Compression {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if bytes.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Detect the compression from the extension of some file.
+    fn from_extension(file: &str) -> Compression {
+        match Path::new(file).extension().and_then(|e| e.to_str()) {
+            Some("gz") | Some("gzip") => Compression::Gzip,
+            Some("bz2") => Compression::Bzip2,
+            Some("xz") => Compression::Xz,
+            Some("zst") | Some("zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Wrap some reader into the decoder of its compression.
+/// The magic bytes take precedence over the given hint.
+pub fn decompress<R: Read + 'static>(reader: R, hint: Compression) -> ConvertResult<Box<dyn Read>> {
+    let mut reader = BufReader::new(reader);
+    let compression = match Compression::from_magic(reader.fill_buf()?) {
+        Compression::None => hint,
+        c => c,
+    };
+    debug!(format!("Reading input with compression {:?}", compression));
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(reader)),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
+        Compression::Zstd => Box::new(ZstdDecoder::with_buffer(reader)?),
+    })
+}
+
+/// Return the encoding of some label or none for auto detection.
+pub fn encoding(label: Option<&str>) -> ConvertResult<Option<&'static Encoding>> {
+    match label {
+        None | Some("auto") => Ok(None),
+        Some(l) => Encoding::for_label(l.trim().as_bytes())
+            .map(Some)
+            .ok_or_else(|| ConvertError::Config(format!("Unknown encoding {}", l))),
+    }
+}
+
+/// Guess the encoding from a sample of the input.
+pub fn detect(sample: &[u8]) -> &'static Encoding {
+    if let Some((enc, _)) = Encoding::for_bom(sample) {
+        return enc;
+    }
+    match ::std::str::from_utf8(sample) {
+        Ok(_) => UTF_8,
+        // the sample may end within some character
+        Err(e) if e.error_len().is_none() => UTF_8,
+        Err(_) => WINDOWS_1252,
+    }
+}
+
+/// Decode some reader with the given or detected encoding.
+pub fn decode<R: Read + 'static>(reader: R, label: Option<&str>) -> ConvertResult<Source> {
+    let mut reader = BufReader::with_capacity(SNIFF, reader);
+    let encoding = match encoding(label)? {
+        Some(e) => e,
+        None => detect(reader.fill_buf()?),
+    };
+    debug!(format!("Reading input as {}", encoding.name()));
+
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .bom_override(true)
+        .build(reader);
+    Ok(BufReader::new(Box::new(decoder)))
+}
+
+/// Open some file, decompress and decode it.
+/// The file `-` is read from stdin.
+pub fn open(file: &str, label: Option<&str>) -> ConvertResult<Source> {
+    if file == "-" {
+        return decode(decompress(io::stdin(), Compression::None)?, label);
+    }
+    let f = File::open(file)
+        .map_err(|e| ConvertError::Import(format!("Failed to import file {} : {}", file, e)))?;
+    decode(decompress(f, Compression::from_extension(file))?, label)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn read(bytes: &[u8], label: Option<&str>) -> String {
+        let mut s = String::new();
+        decode(Cursor::new(bytes.to_vec()), label)
+            .unwrap()
+            .read_to_string(&mut s)
+            .unwrap();
+        s
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(read(b"Z\xe4hlerstand;kWh", None), "Zählerstand;kWh");
+        assert_eq!(read("Zählerstand;kWh".as_bytes(), None), "Zählerstand;kWh");
+        assert_eq!(read(b"\xef\xbb\xbftimestamp;kWh", None), "timestamp;kWh");
+        assert_eq!(read(b"\xff\xfea\x00", None), "a");
+    }
+
+    #[test]
+    fn test_decompress() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut gz = GzEncoder::new(vec![], Default::default());
+        gz.write_all(b"timestamp;kWh").unwrap();
+        let compressed = gz.finish().unwrap();
+
+        let mut s = String::new();
+        decompress(Cursor::new(compressed), Compression::None)
+            .unwrap()
+            .read_to_string(&mut s)
+            .unwrap();
+        assert_eq!(s, "timestamp;kWh");
+
+        let zst = ::zstd::encode_all(&b"timestamp;kWh"[..], 0).unwrap();
+        assert_eq!(Compression::from_magic(&zst), Compression::Zstd);
+        assert_eq!(Compression::from_magic(b"timestamp"), Compression::None);
+        assert_eq!(Compression::from_extension("a.csv.bz2"), Compression::Bzip2);
+        assert_eq!(Compression::from_extension("a.csv"), Compression::None);
+    }
+
+    #[test]
+    fn test_label() {
+        assert_eq!(read(b"Z\xe4hler", Some("latin1")), "Zähler");
+        assert_eq!(read(b"\xef\xbb\xbfZ\xc3\xa4hler", Some("windows-1252")), "Zähler");
+        assert!(decode(Cursor::new(vec![]), Some("klingon")).is_err());
+    }
+}
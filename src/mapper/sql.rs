@@ -0,0 +1,293 @@
+//! Import the result of some query of sqlite databases.
+//!
+//! The columns of the result are mapped by their names like
+//! the columns of a csv file. The query may use the parameter
+//! `:last` which is bound to the greatest time imported from
+//! the database before, so recurring imports only read new rows.
+//! On the first import it is less than any number or text.
+//! Rows from the first rejected one on are read again next time.
+//!
+//! ```toml
+//! format = "sqlite"
+//! measure = "value"
+//! time = "ts"
+//! tags = ["sensor"]
+//! tformat = "%s"
+//!
+//! [sqlite]
+//! query = "SELECT ts, value, sensor FROM readings WHERE ts > :last"
+//! ```
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::f64;
+
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags, Row};
+use serde_json::Value;
+use client::{FieldValue, Message};
+use super::{Driver, Layout, Options, Sink};
+use error::{ConvertError, ConvertResult};
+
+/// The raw text of some column, none if it is null.
+fn text(value: ValueRef) -> Option<String> {
+    match value {
+        ValueRef::Null => None,
+        ValueRef::Integer(i) => Some(i.to_string()),
+        ValueRef::Real(f) => Some(f.to_string()),
+        ValueRef::Text(t) | ValueRef::Blob(t) => Some(String::from_utf8_lossy(t).into_owned()),
+    }
+}
+
+/// Keep some time value to bind it as `:last`.
+fn keep(value: ValueRef) -> Option<Value> {
+    match value {
+        ValueRef::Integer(i) => Some(Value::from(i)),
+        ValueRef::Real(f) => Some(Value::from(f)),
+        ValueRef::Text(_) => text(value).map(Value::from),
+        _ => None,
+    }
+}
+
+/// Compare two kept values like sqlite, numbers before text.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (x.as_i64(), y.as_i64()) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            _ => x.as_f64()
+                .partial_cmp(&y.as_f64())
+                .unwrap_or(Ordering::Equal),
+        },
+        (Value::Number(_), _) => Ordering::Less,
+        (_, Value::Number(_)) => Ordering::Greater,
+        (a, b) => a.as_str().cmp(&b.as_str()),
+    }
+}
+
+/// Positions of the measure, time and named tag columns.
+struct Columns {
+    measure: usize,
+    time: usize,
+    tags: Vec<(String, usize)>,
+}
+
+/// A convenient struct to import sqlite databases.
+///
+/// Every database is queried with the query of the layout
+/// and each row of the result becomes a point.
+#[derive(Debug, Default)]
+pub struct Sql {
+    options: Options,
+    last: RefCell<BTreeMap<String, Value>>,
+}
+
+impl Sql {
+    pub fn new(options: Options) -> Sql {
+        Sql {
+            options,
+            ..Sql::default()
+        }
+    }
+
+    /// Bind `:last` of each database to the given time value.
+    pub fn with_last(self, last: BTreeMap<String, Value>) -> Sql {
+        *self.last.borrow_mut() = last;
+        self
+    }
+
+    /// The greatest time value imported of each database.
+    pub fn last(&self) -> BTreeMap<String, Value> {
+        self.last.borrow().clone()
+    }
+
+    /// Convert a single row of the result.
+    fn convert(&self, layout: &Layout, columns: &Columns, row: &Row) -> ConvertResult<Message> {
+        let get = |i: usize| row.get_ref(i).map_err(|e| ConvertError::Import(format!("{}", e)));
+
+        let measure = get(columns.measure)?;
+        let value = match (layout.types.contains_key(&layout.measure), measure) {
+            (true, v) => layout.parse_value(&layout.measure, &text(v).unwrap_or_default())?,
+            (false, ValueRef::Integer(i)) => FieldValue::Integer(i),
+            (false, ValueRef::Real(f)) => FieldValue::Float(f),
+            (false, ValueRef::Text(_)) => FieldValue::String(text(measure).unwrap_or_default()),
+            (false, _) => {
+                return Err(ConvertError::Import(format!(
+                    "Column {} is null or binary",
+                    layout.measure
+                )))
+            }
+        };
+        let time = text(get(columns.time)?)
+            .ok_or_else(|| ConvertError::Import(format!("Column {} is null", layout.time)))?;
+        let timestamp = layout.parse_time(&time)?;
+
+        // null tags are left out
+        let mut tags = vec![];
+        for &(ref name, i) in &columns.tags {
+            if let Some(t) = text(get(i)?) {
+                tags.push((name.clone(), t));
+            }
+        }
+
+        Ok(Message::new(
+            layout.series.clone(),
+            timestamp,
+            (layout.measure.clone(), value),
+            tags,
+        ))
+    }
+
+    /// Query a single database and pass the rows to the sink.
+    pub fn import_file(&self, file: &str, layout: &Layout, sink: &dyn Sink) -> ConvertResult<()> {
+        let err = |e: ::rusqlite::Error| ConvertError::Import(format!("{}: {}", file, e));
+        let query = layout
+            .sqlite
+            .query
+            .as_ref()
+            .ok_or_else(|| ConvertError::Config("Sqlite databases need a query".into()))?;
+        let db = Connection::open_with_flags(file, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(err)?;
+        let mut stmt = db.prepare(query).map_err(err)?;
+
+        let names: Vec<String> = stmt.column_names().iter().map(|n| n.to_string()).collect();
+        let pos = |name: &str| {
+            names
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| ConvertError::NotFound(name.into()))
+        };
+        let columns = Columns {
+            measure: pos(&layout.measure)?,
+            time: pos(&layout.time)?,
+            tags: layout
+                .tags
+                .iter()
+                .filter_map(|t| pos(t).ok().map(|p| (t.clone(), p)))
+                .collect(),
+        };
+
+        let mut last = self.last.borrow().get(file).cloned();
+        if let Some(i) = stmt.parameter_index(":last").map_err(err)? {
+            debug!(format!("Binding :last of {} to {:?}", file, last));
+            match last {
+                Some(Value::Number(ref n)) if n.is_i64() => {
+                    stmt.raw_bind_parameter(i, n.as_i64().unwrap_or_default())
+                }
+                Some(Value::Number(ref n)) => {
+                    stmt.raw_bind_parameter(i, n.as_f64().unwrap_or_default())
+                }
+                Some(Value::String(ref s)) => stmt.raw_bind_parameter(i, s),
+                _ => stmt.raw_bind_parameter(i, f64::NEG_INFINITY),
+            }.map_err(err)?;
+        }
+
+        let mut driver = Driver::new(&self.options, file, sink);
+        let (mut row, mut rejected) = (0, false);
+        let mut rows = stmt.raw_query();
+        while let Some(r) = rows.next().map_err(err)? {
+            row += 1;
+            let read = driver.row(row, || {
+                let msg = self.convert(layout, &columns, r);
+                // rows after a rejected one are read again with it
+                rejected |= msg.is_err();
+                let time = r.get_ref(columns.time).ok().and_then(keep).filter(|_| !rejected);
+                if let Some(t) = time {
+                    if last.as_ref().is_none_or(|l| compare(&t, l) == Ordering::Greater) {
+                        last = Some(t);
+                    }
+                }
+                Some(msg)
+            });
+            if !read {
+                break;
+            }
+        }
+
+        if let Some(l) = last {
+            self.last.borrow_mut().insert(file.to_owned(), l);
+        }
+        driver.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mapper::test::{temp, Collect};
+    use mapper::SqliteLayout;
+    use std::fs;
+
+    #[test]
+    fn test_incremental() {
+        let path = temp("readings.db");
+        let _ = fs::remove_file(&path);
+        let file = path.to_str().unwrap();
+        let db = Connection::open(file).unwrap();
+        db.execute_batch(
+            "CREATE TABLE readings (ts INTEGER, value REAL, sensor TEXT);
+             INSERT INTO readings VALUES (0, 1.5, 'a'), (900, 2.5, NULL);",
+        ).unwrap();
+
+        let layout = Layout {
+            sqlite: SqliteLayout {
+                query: Some("SELECT * FROM readings WHERE ts > :last ORDER BY ts".into()),
+            },
+            measure: "value".into(),
+            time: "ts".into(),
+            tags: vec!["sensor".into()],
+            tformat: "%s".into(),
+            ..Layout::default()
+        };
+        let sql = Sql::default();
+        let sink = Collect::default();
+        sql.import_file(file, &layout, &sink).unwrap();
+        {
+            let sent = sink.sent.borrow();
+            assert_eq!(sent.len(), 2);
+            assert_eq!(sent[0].value().1, FieldValue::Float(1.5));
+            assert_eq!(sent[0].tags(), &[("sensor".to_string(), "a".to_string())]);
+            assert!(sent[1].tags().is_empty());
+        }
+        assert_eq!(sql.last()[file], Value::from(900));
+
+        // the next run only reads the new rows
+        db.execute("INSERT INTO readings VALUES (1800, 3.5, 'b')", []).unwrap();
+        let sql = Sql::default().with_last(sql.last());
+        let sink = Collect::default();
+        sql.import_file(file, &layout, &sink).unwrap();
+        assert_eq!(sink.sent.borrow().len(), 1);
+        assert_eq!(sql.last()[file], Value::from(1800));
+
+        // rejected rows are read again by the next run
+        db.execute("INSERT INTO readings VALUES (2700, NULL, 'c')", []).unwrap();
+        let sql = Sql::default().with_last(sql.last());
+        let sink = Collect::default();
+        sql.import_file(file, &layout, &sink).unwrap();
+        assert_eq!(*sink.rejected.borrow(), vec![1]);
+        assert_eq!(sql.last()[file], Value::from(1800));
+
+        db.execute("UPDATE readings SET value = 4.5 WHERE ts = 2700", []).unwrap();
+        let sql = Sql::default().with_last(sql.last());
+        let sink = Collect::default();
+        sql.import_file(file, &layout, &sink).unwrap();
+        assert_eq!(sink.sent.borrow()[0].value().1, FieldValue::Float(4.5));
+        assert_eq!(sql.last()[file], Value::from(2700));
+
+        let missing = Layout {
+            sqlite: SqliteLayout::default(),
+            ..layout
+        };
+        assert!(sql.import_file(file, &missing, &sink).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare(&Value::from(2), &Value::from(10)), Ordering::Less);
+        assert_eq!(compare(&Value::from(2.5), &Value::from(2)), Ordering::Greater);
+        assert_eq!(compare(&Value::from(99), &Value::from("1")), Ordering::Less);
+        assert_eq!(
+            compare(&Value::from("2016-01-02"), &Value::from("2016-01-01")),
+            Ordering::Greater
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Checkpoints of imported files to resume an import.
+//!
+//! The state file stores for each file its content hash and the
+//! last row which was written to influxdb. A checkpoint only
+//! advances after the client confirmed all points up to its row.
+//!
+//! ```json
+//! {"files":{"drop/2016.csv":{"hash":"9f86d0…","row":5120,"done":false}}}
+//! ```
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use serde_json;
+use sha2::{Digest, Sha256};
+use client::Message;
+use error::{ConvertError, ConvertResult};
+use super::Sink;
+
+/// How often the state file is written during an import.
+const SAVE: Duration = Duration::from_secs(1);
+
+/// The progress of a single file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Sha256 of the file content.
+    pub hash: String,
+    /// The last row written to influxdb.
+    pub row: usize,
+    /// Whether all rows were written.
+    pub done: bool,
+}
+
+/// The checkpoints of all files by their path.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub files: BTreeMap<String, Checkpoint>,
+    /// The greatest time imported of each sqlite database.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub last: BTreeMap<String, serde_json::Value>,
+}
+
+/// Return the sha256 of some file as hex string.
+pub fn hash(file: &str) -> ConvertResult<String> {
+    let mut f = File::open(file)
+        .map_err(|e| ConvertError::Import(format!("Failed to hash {}: {}", file, e)))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match f.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+impl State {
+    /// Read the state file or return an empty state if it doesn't exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> ConvertResult<State> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| ConvertError::Config(format!("{}: {}", path.display(), e))),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(ConvertError::Config(format!("{}: {}", path.display(), e))),
+        }
+    }
+
+    /// Write the state file, a crash while writing keeps the former one.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ConvertResult<()> {
+        let path = path.as_ref();
+        let content =
+            serde_json::to_string(self).map_err(|e| ConvertError::Config(format!("{}", e)))?;
+        let tmp = PathBuf::from(format!("{}.tmp", path.display()));
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, path).map_err(ConvertError::from)
+    }
+
+    /// Return the row to resume some file with the given hash from
+    /// or none if the file was already imported completely.
+    /// Files with another hash start from the beginning.
+    pub fn resume(&self, file: &str, hash: &str) -> Option<usize> {
+        match self.files.get(file) {
+            Some(c) if c.hash == hash && c.done => None,
+            Some(c) if c.hash == hash => Some(c.row),
+            _ => Some(0),
+        }
+    }
+}
+
+/// Advance the checkpoints of the files once the client
+/// confirmed their points. See `Checkpoints::track`.
+pub struct Checkpoints {
+    confirmed: Arc<AtomicUsize>,
+    path: PathBuf,
+    state: RefCell<State>,
+    hashes: BTreeMap<String, String>,
+    sent: Cell<usize>,
+    // rows passed on with the number of points sent up to them
+    pending: RefCell<VecDeque<(usize, String, usize, bool)>>,
+    saved: Cell<Instant>,
+}
+
+/// A sink which passes the points on and tracks the checkpoints.
+pub struct Tracking<'a> {
+    checkpoints: &'a Checkpoints,
+    inner: &'a dyn Sink,
+}
+
+impl Checkpoints {
+    /// Track the files with the given hashes in the state file.
+    /// `confirmed` counts the points written by the tracked sink.
+    pub fn new(
+        confirmed: Arc<AtomicUsize>,
+        path: &str,
+        state: State,
+        hashes: BTreeMap<String, String>,
+    ) -> Checkpoints {
+        Checkpoints {
+            confirmed,
+            path: PathBuf::from(path),
+            state: RefCell::new(state),
+            hashes,
+            sent: Cell::new(0),
+            pending: RefCell::new(VecDeque::new()),
+            saved: Cell::new(Instant::now()),
+        }
+    }
+
+    /// Advance the checkpoints of all confirmed rows.
+    fn update(&self) -> bool {
+        let confirmed = self.confirmed.load(Ordering::SeqCst);
+        let mut pending = self.pending.borrow_mut();
+        let mut state = self.state.borrow_mut();
+        let mut changed = false;
+        while pending.front().is_some_and(|p| p.0 <= confirmed) {
+            let (_, file, row, done) = pending.pop_front().unwrap_or_default();
+            if let Some(hash) = self.hashes.get(&file) {
+                let checkpoint = Checkpoint {
+                    hash: hash.clone(),
+                    row,
+                    done,
+                };
+                state.files.insert(file, checkpoint);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Whether the client confirmed all points passed on.
+    pub fn confirmed_all(&self) -> bool {
+        self.confirmed.load(Ordering::SeqCst) >= self.sent.get()
+    }
+
+    /// Write the checkpoints confirmed so far, call it
+    /// after the client wrote all points.
+    pub fn save(&self) -> ConvertResult<State> {
+        self.update();
+        let state = self.state.borrow().clone();
+        state.save(&self.path)?;
+        Ok(state)
+    }
+
+    /// Pass the points on to the sink and track the rows.
+    pub fn track<'a>(&'a self, inner: &'a dyn Sink) -> Tracking<'a> {
+        Tracking {
+            checkpoints: self,
+            inner,
+        }
+    }
+}
+
+impl<'a> Sink for Tracking<'a> {
+    fn send(&self, msg: Message) -> ConvertResult<()> {
+        self.inner.send(msg)?;
+        let sent = &self.checkpoints.sent;
+        sent.set(sent.get() + 1);
+        Ok(())
+    }
+
+    fn reject(&self, source: &str, row: usize, err: ConvertError) {
+        self.inner.reject(source, row, err)
+    }
+
+    fn progress(&self, source: &str, row: usize, done: bool) {
+        let c = self.checkpoints;
+        c.pending
+            .borrow_mut()
+            .push_back((c.sent.get(), source.to_owned(), row, done));
+        if c.update() && c.saved.get().elapsed() >= SAVE {
+            if let Err(e) = c.state.borrow().save(&c.path) {
+                error!(format!("Failed to write state {}: {}", c.path.display(), e));
+            }
+            c.saved.set(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Utc;
+    use client::FieldValue;
+    use mapper::test::temp;
+
+    struct Nothing;
+
+    impl Sink for Nothing {
+        fn send(&self, _: Message) -> ConvertResult<()> {
+            Ok(())
+        }
+    }
+
+    fn msg() -> Message {
+        Message::new("s".into(), Utc::now(), ("v".into(), FieldValue::Integer(1)), vec![])
+    }
+
+    #[test]
+    fn test_hash_and_resume() {
+        let hash = hash("assets/test.csv").unwrap();
+        assert_eq!(hash.len(), 64);
+
+        let mut state = State::default();
+        assert_eq!(state.resume("a.csv", &hash), Some(0));
+        state.files.insert(
+            "a.csv".into(),
+            Checkpoint {
+                hash: hash.clone(),
+                row: 12,
+                done: false,
+            },
+        );
+        assert_eq!(state.resume("a.csv", &hash), Some(12));
+        assert_eq!(state.resume("a.csv", "other"), Some(0));
+        state.files.get_mut("a.csv").unwrap().done = true;
+        assert_eq!(state.resume("a.csv", &hash), None);
+
+        let path = temp("state.json");
+        state.save(&path).unwrap();
+        assert_eq!(State::load(&path).unwrap(), state);
+        assert_eq!(State::load("missing.json").unwrap(), State::default());
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let path = temp("checkpoints.json");
+        let confirmed = Arc::new(AtomicUsize::new(0));
+        let hashes = vec![("a.csv".to_string(), "h".to_string())]
+            .into_iter()
+            .collect();
+        let checkpoints = Checkpoints::new(
+            confirmed.clone(),
+            path.to_str().unwrap(),
+            State::default(),
+            hashes,
+        );
+        let sink = checkpoints.track(&Nothing);
+
+        sink.send(msg()).unwrap();
+        sink.progress("a.csv", 2, false);
+        sink.send(msg()).unwrap();
+        sink.progress("a.csv", 3, false);
+        sink.progress("a.csv", 3, true);
+
+        // nothing confirmed yet
+        assert!(checkpoints.save().unwrap().files.is_empty());
+        assert!(!checkpoints.confirmed_all());
+        confirmed.store(1, Ordering::SeqCst);
+        assert_eq!(checkpoints.save().unwrap().files["a.csv"].row, 2);
+        confirmed.store(2, Ordering::SeqCst);
+        let state = checkpoints.save().unwrap();
+        assert_eq!(state.files["a.csv"].row, 3);
+        assert!(state.files["a.csv"].done);
+        assert!(checkpoints.confirmed_all());
+        assert_eq!(State::load(&path).unwrap(), state);
+    }
+}